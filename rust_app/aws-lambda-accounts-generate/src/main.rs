@@ -20,6 +20,7 @@ async fn main() -> Result<(), common_types_accounts::E> {
     let appstate = common_types_accounts::State::make_state().await?;
     let router = Router::new()
                     .route("/generate", routing::post(common_types_accounts::Routes::generate::request))
+                    .route("/generate/personalised", routing::post(common_types_accounts::Routes::generate::personalised_request))
                     .route_layer(ServiceBuilder::new()
                                  .layer(axum_middleware::from_fn_with_state(appstate.clone(), common_types_accounts::Middleware::validate_access_auth::middleware))
                               )
@@ -1,9 +1,27 @@
 use axum::{
+    extract::{Extension, State},
     routing,
     Router,
+    Json,
     middleware as axum_middleware,
 };
 use tower::ServiceBuilder;
+use common_types::Generate::GenerateOption;
+use common_types_accounts::{
+    Response::ServerResponse,
+    State::AppState,
+    Middleware::validate_access_auth::AccessTokenDescription,
+    Routes::generated::content::db::MarkedAnswersPayload,
+};
+use aws_lambda_generate::generate::engine::registry;
+
+fn template_topic(template_id: &str) -> Option<GenerateOption> {
+    registry::find(template_id).map(|template| template.topic.clone())
+}
+
+async fn post_answers_request(access_token: Extension<AccessTokenDescription>, appstate: State<AppState>, payload: Json<MarkedAnswersPayload>) -> Result<(), ServerResponse> {
+    common_types_accounts::Routes::generated::content::post_answers_request(access_token, appstate, payload, template_topic).await
+}
 
 #[tokio::main]
 async fn main() -> Result<(), common_types_accounts::E> {
@@ -20,7 +38,9 @@ async fn main() -> Result<(), common_types_accounts::E> {
                     .route("/generated/content", routing::delete(common_types_accounts::Routes::generated::content::delete_request))
                     .route("/generated/content", routing::get(common_types_accounts::Routes::generated::content::get_request))
                     .route("/generated/content", routing::post(common_types_accounts::Routes::generated::content::post_request))
+                    .route("/generated/content/answers", routing::post(post_answers_request))
                     .route("/generated/content/batch", routing::get(common_types_accounts::Routes::generated::content::get_batch_request))
+                    .route("/generated/content/events", routing::get(common_types_accounts::Routes::generated::content::get_events_request))
                     .route_layer(ServiceBuilder::new()
                                  .layer(axum_middleware::from_fn_with_state(appstate.clone(), common_types_accounts::Middleware::validate_access_auth::middleware))
                               )
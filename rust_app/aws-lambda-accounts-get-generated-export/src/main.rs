@@ -0,0 +1,131 @@
+// Renders a finished paper into formats people can print or edit, lives outside of
+// common-types-accounts since aws-lambda-generate already depends on it
+
+use axum::{
+    extract::{
+        Extension,
+        State,
+        Query,
+    },
+    http::{header, StatusCode},
+    routing,
+    Router,
+    middleware as axum_middleware,
+};
+use serde::Deserialize;
+use tower::ServiceBuilder;
+use common_types_accounts::{
+    Response::{ServerResponse, internal_server_error, status_response},
+    State::AppState,
+    Middleware::validate_access_auth::AccessTokenDescription,
+    Routes::generated::content::{load_generation, StoredGeneration},
+};
+use aws_lambda_generate::generate::{
+    export::{html, latex, markdown},
+    paper::{blob, Paper},
+};
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum LatexDocument {
+    #[default]
+    Paper,
+    MarkScheme,
+}
+
+#[derive(Deserialize)]
+struct LatexQuery {
+    id: String,
+    #[serde(default)]
+    document: LatexDocument,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+    Html,
+    Markdown,
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    id: String,
+    format: ExportFormat,
+}
+
+type Download = ([(header::HeaderName, String); 2], String);
+
+// Only keep characters that are safe in a Content-Disposition filename
+fn download_name(displayname: &str, suffix: &str, extension: &str) -> String {
+    let mut name = displayname.chars()
+                        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+                        .collect::<String>();
+    if name.is_empty() {
+        name.push_str("paper");
+    }
+    format!("{name}{suffix}.{extension}")
+}
+
+async fn load_paper(appstate: &AppState, user_id: i64, id: &str) -> Result<(String, Paper), ServerResponse> {
+    let uuid_job_id = uuid::Uuid::try_parse(id).map_err(|_| status_response(StatusCode::BAD_REQUEST, "Invalid ID"))?;
+    let (generation_details, _, bytes) = match load_generation(appstate, user_id, uuid_job_id).await? {
+        StoredGeneration::Pending(status) => return Err(status_response(StatusCode::CONFLICT, format!("Generation is {status:?}"))),
+        StoredGeneration::Ready(generation_details, finishedon, bytes) => (generation_details, finishedon, bytes),
+    };
+    let (_, paper) = blob::decode(&bytes).map_err(|err| {
+        tracing::error!("Failed to decode paper {uuid_job_id}, {err:?}");
+        internal_server_error("Failed to read paper")
+    })?;
+    Ok((generation_details.displayname, paper))
+}
+
+// GET API endpoint
+#[tracing::instrument(skip(access_token, appstate, query), fields(UserId=%access_token.user_id,request="/generated/content/latex",id=%query.id))]
+async fn latex_request(Extension(access_token): Extension<AccessTokenDescription>, State(appstate): State<AppState>, Query(query): Query<LatexQuery>) -> Result<Download, ServerResponse> {
+    let (displayname, paper) = load_paper(&appstate, access_token.user_id, &query.id).await?;
+    let (document, suffix) = match query.document {
+        LatexDocument::Paper => (latex::render_question_paper(&paper), ""),
+        LatexDocument::MarkScheme => (latex::render_mark_scheme(&paper), "-markscheme"),
+    };
+    Ok(([
+        (header::CONTENT_TYPE, String::from("application/x-tex")),
+        (header::CONTENT_DISPOSITION, format!(r#"attachment; filename="{}""#, download_name(&displayname, suffix, "tex"))),
+    ], document))
+}
+
+// GET API endpoint
+#[tracing::instrument(skip(access_token, appstate, query), fields(UserId=%access_token.user_id,request="/generated/content/export",id=%query.id))]
+async fn export_request(Extension(access_token): Extension<AccessTokenDescription>, State(appstate): State<AppState>, Query(query): Query<ExportQuery>) -> Result<Download, ServerResponse> {
+    let (displayname, paper) = load_paper(&appstate, access_token.user_id, &query.id).await?;
+    let (document, content_type, extension) = match query.format {
+        ExportFormat::Html => (html::render(&paper), "text/html; charset=utf-8", "html"),
+        ExportFormat::Markdown => (markdown::render(&paper), "text/markdown; charset=utf-8", "md"),
+    };
+    Ok(([
+        (header::CONTENT_TYPE, String::from(content_type)),
+        (header::CONTENT_DISPOSITION, format!(r#"attachment; filename="{}""#, download_name(&displayname, "", extension))),
+    ], document))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), common_types_accounts::E> {
+    ::std::env::set_var("AWS_LAMBDA_HTTP_IGNORE_STAGE_IN_PATH", "true");
+
+    tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::INFO)
+            .with_target(false)
+            .without_time()
+            .init();
+
+    let appstate = common_types_accounts::State::make_state().await?;
+    let router = Router::new()
+                    .route("/generated/content/latex", routing::get(latex_request))
+                    .route("/generated/content/export", routing::get(export_request))
+                    .route_layer(ServiceBuilder::new()
+                                 .layer(axum_middleware::from_fn_with_state(appstate.clone(), common_types_accounts::Middleware::validate_access_auth::middleware))
+                              )
+                    .route_layer(axum_middleware::from_fn(common_types_accounts::Middleware::set_cors_headers::middleware))
+                    .with_state(appstate);
+
+    lambda_web::run_hyper_on_lambda(router).await
+}
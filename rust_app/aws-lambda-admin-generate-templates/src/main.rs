@@ -0,0 +1,63 @@
+// Lists the question templates registered in aws-lambda-generate, lives outside of
+// common-types-accounts since aws-lambda-generate already depends on it
+
+use axum::{
+    extract::{
+        Extension,
+        Query,
+    },
+    http::StatusCode,
+    routing,
+    Router,
+    middleware as axum_middleware,
+    Json,
+};
+use serde::Deserialize;
+use tower::ServiceBuilder;
+use common_types::Generate::GenerateOption;
+use common_types_accounts::{
+    Response::{ServerResponse, status_response},
+    Middleware::validate_access_auth::AccessTokenDescription,
+};
+use aws_lambda_generate::generate::engine::registry::{self, Template};
+
+#[derive(Deserialize)]
+struct TemplateQuery {
+    topic: Option<GenerateOption>,
+}
+
+// GET API endpoint
+#[tracing::instrument(skip(access_token, query), fields(UserId=%access_token.user_id,request="/admin/generate/templates"))]
+async fn request(Extension(access_token): Extension<AccessTokenDescription>, Query(query): Query<TemplateQuery>) -> Result<Json<Vec<&'static Template>>, ServerResponse> {
+    if !access_token.has_support_privilege {
+        return Err(status_response(StatusCode::UNAUTHORIZED, "Not Authorised"));
+    }
+
+    let templates = match query.topic {
+        Some(topic) => registry::for_option(&topic).collect(),
+        None => registry::templates().collect(),
+    };
+    Ok(Json(templates))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), common_types_accounts::E> {
+    ::std::env::set_var("AWS_LAMBDA_HTTP_IGNORE_STAGE_IN_PATH", "true");
+
+    tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::INFO)
+            .with_target(false)
+            .without_time()
+            .init();
+
+    let appstate = common_types_accounts::State::make_state().await?;
+    let router = Router::new()
+                    .route("/admin/generate/templates", routing::get(request))
+                    .route_layer(ServiceBuilder::new()
+                                 .layer(axum_middleware::from_fn_with_state(appstate.clone(), common_types_accounts::Middleware::validate_access_auth::middleware))
+                              )
+                    .route_layer(axum_middleware::from_fn(common_types_accounts::Middleware::set_cors_headers::middleware))
+                    .with_state(appstate);
+
+    lambda_web::run_hyper_on_lambda(router).await
+}
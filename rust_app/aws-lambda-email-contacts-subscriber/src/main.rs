@@ -198,7 +198,7 @@ async fn handler(
             if template.template_name == "newslettertemplate" {
                 return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "You cannot send Newsletter template to an individual")) as Box<dyn Error + Send + Sync>);
             }
-            if !Email::is_safe_to_send_to(Arc::clone(&appstate), &event.payload.email).await {
+            if !Email::is_safe_to_send_to(&appstate.postgres, &event.payload.email).await {
                 return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "This address is not safe to send to due to high complaints or bounce count")) as Box<dyn Error + Send + Sync>);
             }
             ses_client
@@ -226,7 +226,7 @@ async fn handler(
             if template.template_name == "newslettertemplate" {
                 return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "You cannot send Newsletter template to an individual")) as Box<dyn Error + Send + Sync>);
             }
-            if !Email::is_safe_to_send_to(Arc::clone(&appstate), &event.payload.email).await {
+            if !Email::is_safe_to_send_to(&appstate.postgres, &event.payload.email).await {
                 return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "This address is not safe to send to due to high complaints or bounce count")) as Box<dyn Error + Send + Sync>);
             }
             ses_client
@@ -0,0 +1,254 @@
+// Run on a schedule, finds generations that have sat in Working, Waiting or Deleting for too long
+// and re-enqueues, fails or hard deletes them
+
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use lambda_runtime::{service_fn, Error as LambdaError, LambdaEvent};
+use lazy_static::lazy_static;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use deadpool_redis::redis::cmd;
+use common_types_accounts::{
+    Constants,
+    Credits::{clear_cached_credits, refund_failed_generation},
+    DB::InsertableDeadLetter,
+    Progress::{self, ProgressEvent},
+    Routes::generated::content::db::GenerationSelectable,
+    Schema::{generation, generationdeadletters, hooked_sql_types::GenerationStatus},
+    State::AppState,
+};
+
+// Only reached through the lazy statics below, which the test harness never touches
+#[cfg_attr(test, allow(dead_code))]
+fn positive_from_env(name: &str, default: i64) -> i64 {
+    let mut value = default;
+    if let Ok(var) = dotenvy::var(name) {
+        match var.parse() {
+            Ok(new_value) if new_value > 0 => {
+                value = new_value;
+                tracing::info!("Using custom {name}: {value}");
+            },
+            _ => tracing::info!("Failed to parse {name}, using default, {value}"),
+        }
+    }
+    value
+}
+
+lazy_static!{
+    // Longer than the generator lambda can run for, anything still Working after this has died
+    static ref STUCK_WORKING_AFTER_SECS: i64 = positive_from_env("STUCK_WORKING_AFTER_SECS", 60 * 30);
+    // The queue message was lost, usually because sending it failed after the row was inserted
+    static ref STUCK_WAITING_AFTER_SECS: i64 = positive_from_env("STUCK_WAITING_AFTER_SECS", 60 * 60);
+    // The generator only drops messages for cancelled jobs, the rows are removed here
+    static ref STUCK_DELETING_AFTER_SECS: i64 = positive_from_env("STUCK_DELETING_AFTER_SECS", 60 * 10);
+    // Should match the generator, a job that has used them all is failed instead of re-enqueued
+    static ref MAX_ATTEMPTS: i32 = positive_from_env("MAX_ATTEMPTS", 5) as i32;
+    // How many jobs of each status are looked at per run
+    static ref REAP_BATCH_SIZE: i64 = positive_from_env("REAP_BATCH_SIZE", 100);
+}
+
+enum Reaped {
+    // Changed or claimed by something else since it was listed
+    Skipped,
+    Requeued,
+    Failed { user_id: i64, refunded: Option<i32> },
+    Deleted,
+}
+
+fn cutoff(secs: i64) -> NaiveDateTime {
+    Utc::now().naive_utc() - TimeDelta::new(secs, 0).unwrap()
+}
+
+async fn list_stuck(conn: &mut AsyncPgConnection, status: GenerationStatus, cutoff: NaiveDateTime) -> QueryResult<Vec<uuid::Uuid>> {
+    generation::table.filter(generation::status.eq(status).and(generation::lastchanged.lt(cutoff)))
+        .order(generation::lastchanged.asc())
+        .limit(*REAP_BATCH_SIZE)
+        .select(generation::jobid)
+        .load(conn)
+        .await
+}
+
+// Puts a Working or Waiting job back on the queue, or fails it once it has no attempts left
+async fn reap_stuck(appstate: &AppState, conn: &mut AsyncPgConnection, job_id: uuid::Uuid, status: GenerationStatus, cutoff: NaiveDateTime) -> QueryResult<Reaped> {
+    conn.build_transaction()
+        .read_write()
+        .run::<Reaped, diesel::result::Error, _>(|conn| async move {
+            let Some((user_id, attempts, generation_details)) = generation::table.filter(
+                                    generation::jobid.eq(job_id)
+                                        .and(generation::status.eq(status.clone()))
+                                        .and(generation::lastchanged.lt(cutoff))
+                                )
+                                .select((generation::userid, generation::attempts, GenerationSelectable::as_select()))
+                                .for_update()
+                                .skip_locked()
+                                .first::<(i64, i32, GenerationSelectable)>(conn)
+                                .await
+                                .optional()? else {
+                return Ok(Reaped::Skipped);
+            };
+            // Rows that were already refunded are requeued dead letters, which an admin has already chosen to
+            // generate for free. They're treated the same, and failing one doesn't refund it a second time
+            let body = generation_details.to_sqs_body(user_id, job_id)
+                            .and_then(|body| serde_json::to_string(&body).ok());
+
+            if let (Some(body), true) = (&body, attempts < *MAX_ATTEMPTS) {
+                diesel::update(generation::table.filter(generation::jobid.eq(job_id)))
+                    .set((
+                        generation::status.eq(GenerationStatus::Waiting),
+                        generation::lastchanged.eq(Utc::now().naive_utc()),
+                    ))
+                    .execute(conn)
+                    .await?;
+                if let Err(queue_err) = appstate.job_queue.send(body.clone()).await {
+                    tracing::error!("Failed to re-enqueue {job_id} due to {queue_err}");
+                    return Err(diesel::result::Error::RollbackTransaction);
+                }
+                return Ok(Reaped::Requeued);
+            }
+
+            let reason = match body {
+                Some(_) => format!("Stuck in {status:?} after {attempts} attempts"),
+                None => format!("Stuck in {status:?} and the stored options are bad"),
+            };
+            diesel::update(generation::table.filter(generation::jobid.eq(job_id)))
+                .set((
+                    generation::status.eq(GenerationStatus::Failed),
+                    generation::failurereason.eq(&reason),
+                    generation::lastchanged.eq(Utc::now().naive_utc()),
+                ))
+                .execute(conn)
+                .await?;
+            diesel::insert_into(generationdeadletters::table)
+                .values(&InsertableDeadLetter {
+                    jobid: job_id,
+                    userid: user_id,
+                    body: body.as_deref().unwrap_or_default(),
+                    reason: &reason,
+                    attempts,
+                    createdat: Utc::now().naive_utc(),
+                })
+                .execute(conn)
+                .await?;
+            let refund = refund_failed_generation(conn, job_id, *Constants::STANDARD_CREDITS_EXPIRE_AFTER_SECS).await?;
+            Ok(Reaped::Failed { user_id, refunded: refund.map(|(_, credits)| credits) })
+        }.scope_boxed())
+        .await
+}
+
+// Removes a cancelled job and anything it left in the paper store
+async fn reap_deleting(appstate: &AppState, conn: &mut AsyncPgConnection, job_id: uuid::Uuid, cutoff: NaiveDateTime) -> QueryResult<Reaped> {
+    conn.build_transaction()
+        .read_write()
+        .run::<Reaped, diesel::result::Error, _>(|conn| async move {
+            let listed: Option<uuid::Uuid> = generation::table.filter(
+                                    generation::jobid.eq(job_id)
+                                        .and(generation::status.eq(GenerationStatus::Deleting))
+                                        .and(generation::lastchanged.lt(cutoff))
+                                )
+                                .select(generation::jobid)
+                                .for_update()
+                                .skip_locked()
+                                .first(conn)
+                                .await
+                                .optional()?;
+            if listed.is_none() {
+                return Ok(Reaped::Skipped);
+            }
+            if let Err(err) = appstate.paper_store.delete(job_id).await {
+                tracing::error!("Failed to delete paper {job_id}, {err}");
+                return Err(diesel::result::Error::RollbackTransaction);
+            }
+            diesel::delete(generation::table.filter(generation::jobid.eq(job_id)))
+                .execute(conn)
+                .await?;
+            Ok(Reaped::Deleted)
+        }.scope_boxed())
+        .await
+}
+
+// Brings the cached status the content endpoint reads in line with what was just done
+async fn fix_cached_status(appstate: &AppState, job_id: uuid::Uuid, reaped: &Reaped) {
+    let Ok(mut redis_conn) = appstate.redis.get().await else {
+        tracing::error!("Failed to get redis connection, {job_id} keeps its cached status until it expires");
+        return;
+    };
+    let generate_redis_key = format!("gen:job:{job_id}");
+    let result = match reaped {
+        Reaped::Skipped => return,
+        Reaped::Requeued => cmd("SET").arg(&[&generate_redis_key, "Waiting", "EX", "1800"]).query_async::<_, ()>(&mut redis_conn).await,
+        Reaped::Failed { user_id, refunded } => {
+            if refunded.is_some() {
+                if let Err(err) = clear_cached_credits(&mut redis_conn, *user_id).await {
+                    tracing::error!("Failed to clear cached credits for {user_id} after refunding, {:?}", err);
+                }
+            }
+            cmd("SET").arg(&[&generate_redis_key, "Failed", "EX", "120"]).query_async::<_, ()>(&mut redis_conn).await
+        },
+        Reaped::Deleted => cmd("DEL").arg(&[&generate_redis_key]).query_async::<_, ()>(&mut redis_conn).await,
+    };
+    if let Err(err) = result {
+        tracing::error!("Failed to fix cached status for {job_id}, {:?}", err);
+    }
+    let event = match reaped {
+        Reaped::Requeued => ProgressEvent::new(GenerationStatus::Waiting, 0),
+        Reaped::Failed { .. } => ProgressEvent::new(GenerationStatus::Failed, 0),
+        _ => return,
+    };
+    if let Err(err) = Progress::publish(&mut redis_conn, job_id, &event).await {
+        tracing::error!("Failed to publish progress for {job_id}, {:?}", err);
+    }
+}
+
+#[tracing::instrument(skip(appstate, event), fields(req_id = %event.context.request_id))]
+async fn handler(appstate: AppState, event: LambdaEvent<serde_json::Value>) -> Result<(), LambdaError> {
+    let mut conn = appstate.postgres.get().await?;
+
+    let stuck = [
+        (GenerationStatus::Working, *STUCK_WORKING_AFTER_SECS),
+        (GenerationStatus::Waiting, *STUCK_WAITING_AFTER_SECS),
+        (GenerationStatus::Deleting, *STUCK_DELETING_AFTER_SECS),
+    ];
+    for (status, after_secs) in stuck {
+        let cutoff = cutoff(after_secs);
+        let job_ids = list_stuck(&mut conn, status.clone(), cutoff).await?;
+        if !job_ids.is_empty() {
+            tracing::info!("Found {} generations stuck in {status:?}", job_ids.len());
+        }
+        for job_id in job_ids {
+            let reaped = match status {
+                GenerationStatus::Deleting => reap_deleting(&appstate, &mut conn, job_id, cutoff).await,
+                _ => reap_stuck(&appstate, &mut conn, job_id, status.clone(), cutoff).await,
+            };
+            match reaped {
+                Ok(reaped) => {
+                    match reaped {
+                        Reaped::Skipped => (),
+                        Reaped::Requeued => tracing::info!("Re-enqueued {job_id}"),
+                        Reaped::Failed { user_id, refunded } => tracing::warn!("Failed {job_id} for {user_id}, refunded {}", refunded.unwrap_or(0)),
+                        Reaped::Deleted => tracing::info!("Deleted {job_id}"),
+                    }
+                    fix_cached_status(&appstate, job_id, &reaped).await;
+                },
+                // Left alone and picked up again on the next run
+                Err(err) => tracing::error!("Failed to reap {job_id}, {err}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), LambdaError> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_target(false)
+        .without_time()
+        .init();
+
+    let appstate = common_types_accounts::State::make_state().await?;
+
+    lambda_runtime::run(service_fn(|event: LambdaEvent<serde_json::Value>| async {
+        handler(appstate.clone(), event).await
+    }))
+    .await
+}
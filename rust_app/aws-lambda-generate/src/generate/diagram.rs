@@ -0,0 +1,267 @@
+/*
+ * Figures for questions that need one (slopes, pulleys, beams, graphs...).
+ *
+ * A Diagram is a declarative list of elements so it can be stored in the paper and drawn by
+ * whoever reads it, render() turns it into an SVG for the exports. Coordinates are in SVG units
+ * with the origin in the bottom left and y going up, the same way round as the questions describe
+ * them. Angles are in degrees anticlockwise from the positive x axis.
+ */
+
+use ::std::fmt::Write;
+use serde::{Deserialize, Serialize};
+
+const STROKE: &str = "#000";
+const FILL: &str = "#e8e8e8";
+const FONT_SIZE: f32 = 12.0;
+const ARROW_MARKER: &str = r#"<defs><marker id="arrow" viewBox="0 0 10 10" refX="9" refY="5" markerWidth="6" markerHeight="6" orient="auto-start-reverse"><path d="M 0 0 L 10 5 L 0 10 z"/></marker></defs>"#;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Diagram {
+    pub width: f32,
+    pub height: f32,
+    pub elements: Vec<Element>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum Element {
+    // Right angled slope rising from `foot`, the angle is marked at the foot
+    Plane { foot: Point, length: f32, angle: f32, label: Option<String> },
+    // Fixed pulley hanging from the top of the diagram
+    Pulley { centre: Point, radius: f32 },
+    Rope { points: Vec<Point> },
+    // Particle or block drawn as a square centred on `centre`
+    Block { centre: Point, size: f32, label: Option<String> },
+    // Horizontal rod starting at `start`, supports are placed by distance along it
+    Beam { start: Point, length: f32, supports: Vec<Support> },
+    Force { at: Point, angle: f32, length: f32, label: Option<String> },
+    Axes { origin: Point, width: f32, height: f32, x_label: String, y_label: String },
+    Histogram { origin: Point, width: f32, height: f32, bars: Vec<Bar>, x_label: String },
+    BoxPlot { origin: Point, width: f32, height: f32, scale: (f32, f32), summary: FiveNumberSummary },
+    Label { at: Point, text: String },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Support {
+    pub at: f32,
+    pub kind: SupportKind,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupportKind {
+    // Knife edge under the beam
+    Pivot,
+    // Vertical string holding the beam up
+    Hanging,
+}
+
+// Class from `start` to `end`, drawn with height proportional to frequency density like the exam boards expect
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Bar {
+    pub start: f32,
+    pub end: f32,
+    pub frequency_density: f32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct FiveNumberSummary {
+    pub minimum: f32,
+    pub lower_quartile: f32,
+    pub median: f32,
+    pub upper_quartile: f32,
+    pub maximum: f32,
+}
+
+impl Point {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+    fn offset(&self, angle: f32, distance: f32) -> Self {
+        let radians = angle.to_radians();
+        Self { x: self.x + distance * radians.cos(), y: self.y + distance * radians.sin() }
+    }
+}
+
+impl Diagram {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self { width, height, elements: Vec::new() }
+    }
+    pub fn with(mut self, element: Element) -> Self {
+        self.elements.push(element);
+        self
+    }
+
+    pub fn render(&self) -> String {
+        let mut svg = String::new();
+        write!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {}" width="{}" height="{}" font-family="sans-serif" font-size="{}" stroke-width="1.5">"#,
+            number(self.width), number(self.height), number(self.width), number(self.height), number(FONT_SIZE)).expect("svg render failed to write to string");
+        svg.push_str(ARROW_MARKER);
+        let canvas = Canvas { height: self.height };
+        for element in self.elements.iter() {
+            canvas.element(&mut svg, element);
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+}
+
+// Flips y so elements can be described with y going up
+struct Canvas {
+    height: f32,
+}
+
+impl Canvas {
+    fn point(&self, point: Point) -> String {
+        format!("{},{}", number(point.x), number(self.height - point.y))
+    }
+    fn line(&self, svg: &mut String, from: Point, to: Point, extra: &str) {
+        write!(svg, r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{STROKE}"{extra}/>"#,
+            number(from.x), number(self.height - from.y), number(to.x), number(self.height - to.y)).expect("svg render failed to write to string");
+    }
+    fn polygon(&self, svg: &mut String, points: &[Point], fill: &str) {
+        let points = points.iter().map(|point| self.point(*point)).collect::<Vec<String>>().join(" ");
+        write!(svg, r#"<polygon points="{points}" fill="{fill}" stroke="{STROKE}"/>"#).expect("svg render failed to write to string");
+    }
+    fn rect(&self, svg: &mut String, bottom_left: Point, width: f32, height: f32, fill: &str) {
+        write!(svg, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{fill}" stroke="{STROKE}"/>"#,
+            number(bottom_left.x), number(self.height - bottom_left.y - height), number(width), number(height)).expect("svg render failed to write to string");
+    }
+    fn text(&self, svg: &mut String, at: Point, anchor: &str, text: &str) {
+        write!(svg, r#"<text x="{}" y="{}" text-anchor="{anchor}">{}</text>"#, number(at.x), number(self.height - at.y), escape(text)).expect("svg render failed to write to string");
+    }
+
+    fn element(&self, svg: &mut String, element: &Element) {
+        match element {
+            Element::Plane { foot, length, angle, label } => {
+                let top = foot.offset(*angle, *length);
+                let corner = Point::new(top.x, foot.y);
+                self.polygon(svg, &[*foot, corner, top], FILL);
+                let radius = (length * 0.15).min(30.0);
+                let arc_end = foot.offset(*angle, radius);
+                write!(svg, r#"<path d="M {} A {} {} 0 0 0 {}" fill="none" stroke="{STROKE}"/>"#,
+                    self.point(Point::new(foot.x + radius, foot.y)), number(radius), number(radius), self.point(arc_end)).expect("svg render failed to write to string");
+                if let Some(label) = label {
+                    self.text(svg, foot.offset(angle / 2.0, radius + FONT_SIZE), "middle", label);
+                }
+            },
+            Element::Pulley { centre, radius } => {
+                self.line(svg, *centre, Point::new(centre.x, self.height), "");
+                self.line(svg, Point::new(centre.x - radius, self.height - 1.0), Point::new(centre.x + radius, self.height - 1.0), r#" stroke-width="3""#);
+                write!(svg, r#"<circle cx="{}" cy="{}" r="{}" fill="{FILL}" stroke="{STROKE}"/>"#,
+                    number(centre.x), number(self.height - centre.y), number(*radius)).expect("svg render failed to write to string");
+            },
+            Element::Rope { points } => {
+                let points = points.iter().map(|point| self.point(*point)).collect::<Vec<String>>().join(" ");
+                write!(svg, r#"<polyline points="{points}" fill="none" stroke="{STROKE}"/>"#).expect("svg render failed to write to string");
+            },
+            Element::Block { centre, size, label } => {
+                self.rect(svg, Point::new(centre.x - size / 2.0, centre.y - size / 2.0), *size, *size, FILL);
+                if let Some(label) = label {
+                    self.text(svg, Point::new(centre.x, centre.y - FONT_SIZE / 3.0), "middle", label);
+                }
+            },
+            Element::Beam { start, length, supports } => {
+                let thickness = 6.0;
+                self.rect(svg, Point::new(start.x, start.y - thickness / 2.0), *length, thickness, FILL);
+                for support in supports.iter() {
+                    let x = start.x + support.at;
+                    match support.kind {
+                        SupportKind::Pivot => {
+                            let top = start.y - thickness / 2.0;
+                            self.polygon(svg, &[Point::new(x, top), Point::new(x - 8.0, top - 14.0), Point::new(x + 8.0, top - 14.0)], FILL);
+                        },
+                        SupportKind::Hanging => {
+                            self.line(svg, Point::new(x, start.y + thickness / 2.0), Point::new(x, self.height), "");
+                        },
+                    }
+                }
+            },
+            Element::Force { at, angle, length, label } => {
+                let end = at.offset(*angle, *length);
+                self.line(svg, *at, end, r#" marker-end="url(#arrow)""#);
+                if let Some(label) = label {
+                    self.text(svg, end.offset(*angle, FONT_SIZE), "middle", label);
+                }
+            },
+            Element::Axes { origin, width, height, x_label, y_label } => {
+                self.axes(svg, *origin, *width, *height);
+                self.text(svg, Point::new(origin.x + width, origin.y - FONT_SIZE * 1.5), "end", x_label);
+                self.text(svg, Point::new(origin.x + 4.0, origin.y + height), "start", y_label);
+            },
+            Element::Histogram { origin, width, height, bars, x_label } => {
+                self.histogram(svg, *origin, *width, *height, bars);
+                self.text(svg, Point::new(origin.x + width, origin.y - FONT_SIZE * 2.5), "end", x_label);
+                self.text(svg, Point::new(origin.x + 4.0, origin.y + height), "start", "Frequency density");
+            },
+            Element::BoxPlot { origin, width, height, scale, summary } => self.box_plot(svg, *origin, *width, *height, *scale, summary),
+            Element::Label { at, text } => self.text(svg, *at, "middle", text),
+        }
+    }
+
+    fn axes(&self, svg: &mut String, origin: Point, width: f32, height: f32) {
+        self.line(svg, origin, Point::new(origin.x + width, origin.y), r#" marker-end="url(#arrow)""#);
+        self.line(svg, origin, Point::new(origin.x, origin.y + height), r#" marker-end="url(#arrow)""#);
+    }
+
+    fn histogram(&self, svg: &mut String, origin: Point, width: f32, height: f32, bars: &[Bar]) {
+        self.axes(svg, origin, width, height);
+        let low = bars.iter().map(|bar| bar.start).fold(f32::INFINITY, f32::min);
+        let high = bars.iter().map(|bar| bar.end).fold(f32::NEG_INFINITY, f32::max);
+        let tallest = bars.iter().map(|bar| bar.frequency_density).fold(0.0, f32::max);
+        if bars.is_empty() || high <= low || tallest <= 0.0 {
+            return;
+        }
+        // Leave room for the arrow heads
+        let x_scale = width * 0.9 / (high - low);
+        let y_scale = height * 0.9 / tallest;
+        for bar in bars.iter() {
+            let left = origin.x + (bar.start - low) * x_scale;
+            self.rect(svg, Point::new(left, origin.y), (bar.end - bar.start) * x_scale, bar.frequency_density * y_scale, FILL);
+            self.text(svg, Point::new(left, origin.y - FONT_SIZE), "middle", &number(bar.start));
+        }
+        self.text(svg, Point::new(origin.x + (high - low) * x_scale, origin.y - FONT_SIZE), "middle", &number(high));
+    }
+
+    fn box_plot(&self, svg: &mut String, origin: Point, width: f32, height: f32, (low, high): (f32, f32), summary: &FiveNumberSummary) {
+        if high <= low {
+            return;
+        }
+        let x = |value: f32| origin.x + (value - low) * width / (high - low);
+        let middle = origin.y + height / 2.0;
+        let box_height = height / 2.0;
+
+        // Scale along the bottom with the ends labelled
+        self.line(svg, origin, Point::new(origin.x + width, origin.y), "");
+        for value in [low, high] {
+            self.line(svg, Point::new(x(value), origin.y), Point::new(x(value), origin.y - 4.0), "");
+            self.text(svg, Point::new(x(value), origin.y - FONT_SIZE - 2.0), "middle", &number(value));
+        }
+
+        self.line(svg, Point::new(x(summary.minimum), middle), Point::new(x(summary.lower_quartile), middle), "");
+        self.line(svg, Point::new(x(summary.upper_quartile), middle), Point::new(x(summary.maximum), middle), "");
+        for whisker in [summary.minimum, summary.maximum] {
+            self.line(svg, Point::new(x(whisker), middle - box_height / 4.0), Point::new(x(whisker), middle + box_height / 4.0), "");
+        }
+        self.rect(svg, Point::new(x(summary.lower_quartile), middle - box_height / 2.0), x(summary.upper_quartile) - x(summary.lower_quartile), box_height, FILL);
+        self.line(svg, Point::new(x(summary.median), middle - box_height / 2.0), Point::new(x(summary.median), middle + box_height / 2.0), "");
+    }
+}
+
+// Two decimal places at most, SVGs get big quickly otherwise
+fn number(value: f32) -> String {
+    let formatted = format!("{value:.2}");
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" => String::from("0"),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
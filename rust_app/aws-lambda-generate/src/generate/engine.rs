@@ -1,12 +1,174 @@
-use common_types::Generate::GenerateOption;
+use ::std::collections::HashSet;
+use rand::Rng;
+use common_types::Generate::{GenerateOption, PaperComposition, PaperOrdering};
+
+use super::helper;
+use super::question::{QuestionMetadata, QuestionType};
+use super::questionstacker::Stacker;
+use personalise::Planner;
+use registry::Template;
 
 pub mod math;
+pub mod personalise;
+pub mod registry;
 
 pub type GenerateResult<T> = Result<T, GenerateFailure>;
 
+// How many times a question that has been seen before is rolled again before we give up and use it
+pub const MAX_REROLLS: usize = 10;
+
 #[derive(Debug)]
 pub enum GenerateFailure {
     InvalidOption( GenerateOption ),
 }
 
-pub const GENERATE_QUESTIONS_PER_TOPIC: usize = 3;
+// Makes every paper generated on this thread from now on reproducible
+pub fn seed(seed: u64) {
+    helper::reseed(seed);
+}
+
+// How many root questions each topic gets, every topic gets at least one and the rest
+// of the paper is shared out by weight (largest remainder goes first)
+pub fn allocate_questions(options: &[GenerateOption], composition: &PaperComposition) -> Vec<usize> {
+    let mut allocation = vec![0; options.len()];
+    let mut remaining = composition.length;
+    for slot in allocation.iter_mut() {
+        if remaining == 0 {
+            break;
+        }
+        *slot = 1;
+        remaining -= 1;
+    }
+
+    let weights = options.iter().map(|option| composition.weight_of(option) as usize).collect::<Vec<usize>>();
+    let total_weight: usize = weights.iter().sum();
+    if remaining == 0 || total_weight == 0 {
+        return allocation;
+    }
+
+    let mut remainders = Vec::with_capacity(options.len());
+    let mut handed_out = 0;
+    for (index, weight) in weights.iter().enumerate() {
+        let share = remaining * weight;
+        allocation[index] += share / total_weight;
+        handed_out += share / total_weight;
+        remainders.push((share % total_weight, index));
+    }
+    // Ties go to whichever topic was asked for first
+    remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    for (_, index) in remainders.into_iter().take(remaining - handed_out) {
+        allocation[index] += 1;
+    }
+    allocation
+}
+
+// Template id plus a hash of the template's parameters, falls back to hashing the text of the questions
+// for templates that don't declare any parameters. Only the text, the metadata carries the seed which
+// would make every run look new
+pub fn fingerprint(template: &Template, stacker: &Stacker) -> String {
+    let hash = if stacker.parameters().is_empty() {
+        fnv1a(stacker.raw_text().as_bytes())
+    } else {
+        fnv1a(&stacker.parameters().iter().flat_map(|parameter| parameter.to_le_bytes()).collect::<Vec<u8>>())
+    };
+    format!("{}:{hash:016x}", template.id)
+}
+
+// Fingerprints get stored, so this needs to hash the same on every build unlike DefaultHasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+// Runs a template on its own seed so any question can be made again from its metadata
+pub fn run(template: &Template, seed: u64, difficulty: Option<u8>) -> Stacker {
+    helper::reseed(seed);
+    let mut questionstacker = (template.generator)();
+    questionstacker.set_metadata(QuestionMetadata {
+        topic: template.topic.clone(),
+        template_id: template.id.to_string(),
+        seed,
+        difficulty: difficulty.unwrap_or(*template.difficulty.start()),
+        spec_code: format!("{} {}", template.spec_code, template.spec_reference),
+        tags: template.tags.iter().map(ToString::to_string).collect(),
+    });
+    questionstacker
+}
+
+fn generate_for_option(generate_option: &GenerateOption, seen: &mut HashSet<String>) -> GenerateResult<(Vec<QuestionType>, String)> {
+    generate_with(generate_option, seen, || registry::choose(generate_option, None).map(|template| (template, None)))
+}
+
+fn generate_with(generate_option: &GenerateOption, seen: &mut HashSet<String>, choose: impl Fn() -> Option<(&'static Template, Option<u8>)>) -> GenerateResult<(Vec<QuestionType>, String)> {
+    let mut attempt = 0;
+    loop {
+        let (template, difficulty) = choose().ok_or(GenerateFailure::InvalidOption( generate_option.clone() ))?;
+        let seed = helper::with_rng(|rng| rng.gen());
+        let questionstacker = run(template, seed, difficulty);
+        let fingerprint = fingerprint(template, &questionstacker);
+        attempt += 1;
+        if seen.insert(fingerprint.clone()) || attempt > MAX_REROLLS {
+            return Ok((questionstacker.consume_get_questions(), fingerprint));
+        }
+    }
+}
+
+// Fingerprints in `avoid` (and any repeats within the paper itself) are rolled again
+pub fn generate_from_composition(options: &[GenerateOption], composition: &PaperComposition, avoid: &HashSet<String>) -> GenerateResult<(Vec<QuestionType>, Vec<String>)> {
+    // Make sure every option has something registered before we start rolling templates
+    for generate_option in options.iter() {
+        if registry::for_option(generate_option).next().is_none() {
+            return Err(GenerateFailure::InvalidOption( generate_option.clone() ));
+        }
+    }
+
+    let allocation = allocate_questions(options, composition);
+    let mut seen = avoid.clone();
+    let mut questions = Vec::new();
+    let mut fingerprints = Vec::new();
+    let mut push = |generate_option: &GenerateOption| -> GenerateResult<()> {
+        let (mut generated, fingerprint) = generate_for_option(generate_option, &mut seen)?;
+        questions.append(&mut generated);
+        fingerprints.push(fingerprint);
+        Ok(())
+    };
+
+    match composition.ordering {
+        PaperOrdering::Grouped => {
+            for (generate_option, amount) in options.iter().zip(allocation.iter()) {
+                for _ in 0..*amount {
+                    push(generate_option)?;
+                }
+            }
+        },
+        PaperOrdering::Interleaved => {
+            let rounds = allocation.iter().copied().max().unwrap_or(0);
+            for round in 0..rounds {
+                for (generate_option, amount) in options.iter().zip(allocation.iter()) {
+                    if round < *amount {
+                        push(generate_option)?;
+                    }
+                }
+            }
+        },
+    }
+
+    Ok((questions, fingerprints))
+}
+
+// Topics are picked from the user's history instead of being asked for, returns the picked
+// topics alongside the questions so they can be recorded against the paper
+pub fn generate_personalised(planner: &Planner, length: usize, avoid: &HashSet<String>) -> GenerateResult<(Vec<QuestionType>, Vec<String>, Vec<GenerateOption>)> {
+    let mut seen = avoid.clone();
+    let mut questions = Vec::new();
+    let mut fingerprints = Vec::new();
+    let mut topics: Vec<GenerateOption> = Vec::new();
+    for topic in planner.topics(length) {
+        let (mut generated, fingerprint) = generate_with(&topic, &mut seen, || planner.choose_template(&topic))?;
+        questions.append(&mut generated);
+        fingerprints.push(fingerprint);
+        if !topics.contains(&topic) {
+            topics.push(topic);
+        }
+    }
+    Ok((questions, fingerprints, topics))
+}
@@ -8,25 +8,44 @@
  *
  */
 
+use common_types::Generate::GenerateOption;
 use crate::generate::{
+    engine::registry::Template,
     formatter::{self, LABEL_MS, LABEL_MS_RAW, LABEL_AS, LABEL_AS_RAW, LABEL_M, LABEL_M_RAW},
     helper,
+    locale,
     oncelabel::OnceLabel,
     questionstacker::Stacker,
-    question::{Question, QuestionHeader, MarkScheme},
+    question::{Question, QuestionHeader, MarkScheme, MarkStep, Mark},
+};
+
+pub const TEMPLATE: Template = Template {
+    id: "mechanics.suvat.t1",
+    topic: GenerateOption::SUVAT,
+    spec_code: "9MA0",
+    spec_reference: "7.4",
+    difficulty: 2..=3,
+    expected_marks: 6,
+    authored_on: "25/05/2024 11:35",
+    description: "This question models an object as a particle with constant acceleration and a velocity.",
+    weight: 1,
+    tags: &["kinematics", "constant-acceleration", "vectors"],
+    reserved_labels: &["T", "O"],
+    generator: generate,
 };
 
 pub fn generate() -> Stacker {
     let mut stacker = Stacker::new();
-    let mut oncelabel = OnceLabel::new();
+    let mut oncelabel = OnceLabel::for_template(&TEMPLATE);
 
-    let (p_elision, p_name) = helper::get_particle_object_name();
-    let (p_label, p_label_raw) = oncelabel.next_label_raw();
+    let p_object = helper::get_particle_object_name();
+    let p = oncelabel.next_label().expect("a single label is always free");
     let t_0 = helper::gen_range_i32(0, 6);
     let t_1 = helper::gen_range_i32_except(0, 6, t_0);
 
     let (a_i, a_j) = (helper::gen_range_i32_except(-10, 10, 0), helper::gen_range_i32_except(-10, 10, 0));
     let (v_i, v_j) = (helper::gen_range_i32_except(-10, 10, 0), helper::gen_range_i32_except(-10, 10, 0));
+    stacker.fingerprint_parameters(&[t_0, t_1, a_i, a_j, v_i, v_j]);
 
     let formatted_a = formatter::format_i32_group_labelled_raw(&[a_i, a_j]);
     let formatted_v = formatter::format_i32_group_labelled_raw(&[v_i, v_j]);
@@ -35,117 +54,142 @@ pub fn generate() -> Stacker {
     let formatted_raw_v = formatter::format_i32_group_labelled_raw2(&[v_i, v_j]);
 
     // (1) Root question body
-    let rq_1 = Question::new(
-        QuestionHeader::new(
-            format!(r#"{p_elision} {p_name}, {p_label_raw}, is modelled as a particle and moves with constant acceleration {formatted_raw_a}{LABEL_AS_RAW}. At time T = {t_0} seconds {p_label_raw} is moving with velocity {formatted_raw_v}{LABEL_MS_RAW}"#),
-            format!(r#"{p_elision} {p_name}, \({p_label}\), is modelled as a particle and moves with constant acceleration \({formatted_a}{LABEL_AS}\). At time \(\mathbf{{T}} = {t_0}\) seconds \({p_label}\) is moving with velocity \({formatted_v}{LABEL_MS}\)"#),
-        )
-    );
+    let (rq_1_raw, rq_1_latex) = locale::pair("mechanics.suvat.particle", &[
+        ("object", &p_object, &p_object),
+        ("p", &p.raw, &p.latex),
+        ("a", &format!("{formatted_raw_a}{LABEL_AS_RAW}"), &format!("{formatted_a}{LABEL_AS}")),
+        ("t0", &t_0, &t_0),
+        ("v", &format!("{formatted_raw_v}{LABEL_MS_RAW}"), &format!("{formatted_v}{LABEL_MS}")),
+    ]);
+    let rq_1 = Question::new(QuestionHeader::new(rq_1_raw, rq_1_latex));
     stacker.next_root_question(rq_1);
     
+    // v = I(a) + c, where c is found from the velocity at t_0
+    let (c_i, c_j) = (v_i - a_i * t_0, v_j - a_j * t_0);
+    let formatted_c = formatter::format_i32_group_labelled_raw(&[c_i, c_j]);
+    let formatted_raw_c = formatter::format_i32_group_labelled_raw2(&[c_i, c_j]);
+
     // (1.a) Sub question 
+    let (integrate_raw, integrate) = locale::pair("mechanics.suvat.integrate_acceleration", &[]);
+    let (constant_raw, constant) = locale::pair("mechanics.suvat.velocity_constant", &[("t0", &t_0, &t_0)]);
+    let (substitute_raw, substitute) = locale::pair("mechanics.suvat.substitute_velocity", &[("t", &t_1, &t_1)]);
+    let (rq_1_a_raw, rq_1_a_latex) = locale::pair("mechanics.suvat.t1.velocity", &[("p", &p.raw, &p.latex), ("t", &t_1, &t_1)]);
     let rq_1_a = Question::from_header_and_scheme(
-        QuestionHeader::new(
-            format!(r#"Find the velocity of {p_label_raw} at T = {t_1} seconds."#), 
-            format!(r#"Find the velocity of \({p_label}\) at \(\mathbf{{T}} = {t_1}\) seconds."#)
-        ),
-        MarkScheme::from(
-            format!(r#"Understand the integral of acceleration is velocity, v = I(a) + C. Solving for constant, C, at T = {t_0}, and finding a complete equation for v. Finally, substituting T = {t_1} using the found equation for v to get v = {}{LABEL_MS_RAW}"#, {
-                // v = I(a) + C
-                let (ic, ij) = (v_i - a_i * t_0, v_j - a_j * t_0);
-                let (iv, jv) = (a_i * t_1 + ic, a_j * t_1 + ij);
-                formatter::format_i32_group_labelled_raw2(&[iv, jv])
-            }),
-            format!(r#"Understand the integral of acceleration is velocity, \(\mathbf{{v}}=\int{{\mathbf{{a}}}}\,dt+\mathbf{{c}}\). Solving for constant, \(\mathbf{{c}}\), at \(\mathbf{{T}} = {t_1}\) using the found equation for \(\mathbf{{v}}\) to get \(\mathbf{{v}}={}{LABEL_MS}\)"#, {
-                // v = I(a) + C
-                let (ic, jc) = (v_i - a_i * t_0, v_j - a_j * t_0);
-                let (iv, jv) = (a_i * t_1 + ic, a_j * t_1 + jc);
-                formatter::format_i32_group_labelled_raw(&[iv, jv])
-            })
-        )
+        QuestionHeader::new(rq_1_a_raw, rq_1_a_latex),
+        MarkScheme::from_steps(vec![
+            MarkStep::new(integrate_raw, integrate).working(
+                format!(r#"v = I(a) + C = {formatted_raw_a}T + C"#),
+                format!(r#"\(\mathbf{{v}}=\int{{\mathbf{{a}}}}\,dt+\mathbf{{c}}={formatted_a}\mathbf{{T}}+\mathbf{{c}}\)"#),
+            ).mark(Mark::M1),
+            MarkStep::new(constant_raw, constant).working(
+                format!(r#"{formatted_raw_v} = {formatted_raw_a} x {t_0} + C"#),
+                format!(r#"\({formatted_v}={formatted_a}\times{t_0}+\mathbf{{c}}\)"#),
+            ).result(
+                format!(r#"C = {formatted_raw_c}"#),
+                format!(r#"\(\mathbf{{c}}={formatted_c}\)"#),
+            ).mark(Mark::M1),
+            MarkStep::new(substitute_raw, substitute).working(
+                format!(r#"v = {formatted_raw_a} x {t_1} + {formatted_raw_c}"#),
+                format!(r#"\(\mathbf{{v}}={formatted_a}\times{t_1}+{formatted_c}\)"#),
+            ).result(
+                {
+                    let (iv, jv) = (a_i * t_1 + c_i, a_j * t_1 + c_j);
+                    format!(r#"v = {}{LABEL_MS_RAW}"#, formatter::format_i32_group_labelled_raw2(&[iv, jv]))
+                },
+                {
+                    let (iv, jv) = (a_i * t_1 + c_i, a_j * t_1 + c_j);
+                    format!(r#"\(\mathbf{{v}}={}{LABEL_MS}\)"#, formatter::format_i32_group_labelled_raw(&[iv, jv]))
+                },
+            ).mark(Mark::A1),
+        ])
     );
     stacker.next_root_sub_question(rq_1_a);
 
     if helper::coin_flip() {
-        if helper::coin_flip() {
-            // (1.b) Relative to origin
-            let t_2 = helper::gen_range_i32(1, 30);
-            let (i_s, j_s) = (helper::gen_range_i32_except(-100, 100, 0), helper::gen_range_i32_except(-100, 100, 0));
-            
-            let formatted_s = formatter::format_i32_group_labelled_raw(&[i_s, j_s]);
+        // s = I(v) + k, where k is the position at T = 0
+        let t_2 = helper::gen_range_i32(1, 30);
+        let (i_s, j_s) = (helper::gen_range_i32_except(-100, 100, 0), helper::gen_range_i32_except(-100, 100, 0));
 
-            let formatted_raw_s = formatter::format_i32_group_labelled_raw2(&[i_s, j_s]);
+        let formatted_s = formatter::format_i32_group_labelled_raw(&[i_s, j_s]);
+        let formatted_raw_s = formatter::format_i32_group_labelled_raw2(&[i_s, j_s]);
 
+        let (is, js) = {
+            let (ia, ic, is, ja, jc, js, t2) = (a_i as f32, c_i as f32, i_s as f32, a_j as f32, c_j as f32, j_s as f32, t_2 as f32);
+            (0.5 * ia * t2 * t2 + ic * t2 + is, 0.5 * ja * t2 * t2 + jc * t2 + js)
+        };
+        let displacement_steps = |relative_raw: &str, relative: &str| vec![
+            {
+                let (raw, latex) = locale::pair("mechanics.suvat.integrate_velocity", &[]);
+                MarkStep::new(raw, latex)
+            }.working(
+                format!(r#"s = I(v) + K = 1/2{formatted_raw_a}T^2 + {formatted_raw_c}T + K"#),
+                format!(r#"\(\mathbf{{s}}=\int{{\mathbf{{v}}}}\,dt+\mathbf{{k}}=\frac{{1}}{{2}}{formatted_a}\mathbf{{T}}^2+{formatted_c}\mathbf{{T}}+\mathbf{{k}}\)"#),
+            ).mark(Mark::M1),
+            {
+                let (raw, latex) = locale::pair("mechanics.suvat.displacement_constant", &[("relative", &relative_raw, &relative)]);
+                MarkStep::new(raw, latex)
+            }.result(
+                format!(r#"K = {formatted_raw_s}"#),
+                format!(r#"\(\mathbf{{k}}={formatted_s}\)"#),
+            ).mark(Mark::M1),
+            {
+                let (raw, latex) = locale::pair("mechanics.suvat.substitute_displacement", &[("t", &t_2, &t_2)]);
+                MarkStep::new(raw, latex)
+            }.working(
+                format!(r#"s = 1/2{formatted_raw_a}({t_2})^2 + {formatted_raw_c}({t_2}) + {formatted_raw_s}"#),
+                format!(r#"\(\mathbf{{s}}=\frac{{1}}{{2}}{formatted_a}({t_2})^2+{formatted_c}({t_2})+{formatted_s}\)"#),
+            ).result(
+                format!(r#"s = {}{LABEL_M_RAW}"#, formatter::format_f32_group_labelled_raw2(&[is, js])),
+                format!(r#"\(\mathbf{{s}}={}{LABEL_M}\)"#, formatter::format_f32_group_labelled_raw(&[is, js])),
+            ).mark(Mark::A1),
+        ];
+
+        if helper::coin_flip() {
+            // (1.b) Relative to origin
+            let (header_raw, header) = locale::pair("mechanics.suvat.t1.origin_position", &[
+                ("p", &p.raw, &p.latex),
+                ("s", &format!("{formatted_raw_s}{LABEL_M_RAW}"), &format!("{formatted_s}{LABEL_M}")),
+            ]);
+            let (body_raw, body) = locale::pair("mechanics.suvat.t1.origin_displacement", &[("p", &p.raw, &p.latex), ("t", &t_2, &t_2)]);
             let rq_1_b = Question::from(
-                QuestionHeader::new(
-                    format!(r#"The position vector of {p_label_raw} relative to a fixed origin O is {formatted_raw_s}{LABEL_M_RAW} at T = 0."#),
-                    format!(r#"The position vector of \({p_label}\) relative to a fixed origin \(\mathbf{{O}}\) is \({formatted_s}{LABEL_M}\) at \(\mathbf{{T}}=0\)."#)
-                ),
-                format!(r#"Find the position vector of {p_label_raw} relative to O at time T = {t_2} seconds."#),
-                format!(r#"Find the position vector of \({p_label}\) relative to \(\mathbf{{O}}\) at time \(\mathbf{{T}}={t_2}\) seconds."#),
-                MarkScheme::from(
-                    format!(r#"Understand the integral of velocity is displacement, s = I(v) + K. Solving for constant, K, at T = 0, and finding a complete equation for s. Finally, substituting T = {t_2} using the found equation for s to get s = {}{LABEL_M_RAW}"#, {
-                        // v = I(a) + c
-                        // s = I(v) + k
-                        let (ic, jc) = (v_i - a_i * t_0, v_j - a_j * t_0);
-                        let (iv0, ic, is, jv0, jc, js, t2) = (a_i as f32, ic as f32, i_s as f32, a_j as f32, jc as f32, j_s as f32, t_2 as f32);
-                        let (is, js) = (0.5 * iv0 * t2 * t2 + ic * t2 + is, 0.5 * jv0 * t2 * t2 + jc * t2 + js);
-                        formatter::format_f32_group_labelled_raw2(&[is, js])
-                    }),
-                    format!(r#"Understand the integral of velocity is displacement, \(\mathbf{{s}}=\int{{\mathbf{{v}}}}\,dt+\mathbf{{k}}\). Solving for constant, \(\mathbf{{k}}\), at \(\mathbf{{T}} = {t_2}\) using the found equation for \(\mathbf{{s}}\) to get \(\mathbf{{s}}={}{LABEL_M}\)"#, {
-                        // v = I(a) + c
-                        // s = I(v) + k
-                        let (ic, jc) = (v_i - a_i * t_0, v_j - a_j * t_0);
-                        let (iv0, ic, is, jv0, jc, js, t2) = (a_i as f32, ic as f32, i_s as f32, a_j as f32, jc as f32, j_s as f32, t_2 as f32);
-                        let (is, js) = (0.5 * iv0 * t2 * t2 + ic * t2 + is, 0.5 * jv0 * t2 * t2 + jc * t2 + js);
-                        formatter::format_f32_group_labelled_raw(&[is, js])
-                    })
-                )
+                QuestionHeader::new(header_raw, header),
+                body_raw,
+                body,
+                MarkScheme::from_steps(displacement_steps("O", r#"\mathbf{O}"#))
             );
             stacker.next_root_sub_question(rq_1_b);
         } else {
             // (1.b) Relative to a random vector
-            let t_2 = helper::gen_range_i32(1, 30);
+            stacker.tag("relative-position");
             let (r_i_s, r_j_s) = (helper::gen_range_i32_except(-100, 100, 0), helper::gen_range_i32_except(-100, 100, 0));
-            let (i_s, j_s) = (helper::gen_range_i32_except(-100, 100, 0), helper::gen_range_i32_except(-100, 100, 0));
 
             let formatted_r_s = formatter::format_i32_group_labelled_raw(&[r_i_s, r_j_s]);
-            let formatted_s = formatter::format_i32_group_labelled_raw(&[i_s, j_s]);
-
             let formatted_raw_r_s = formatter::format_i32_group_labelled_raw2(&[r_i_s, r_j_s]);
-            let formatted_raw_s = formatter::format_i32_group_labelled_raw2(&[i_s, j_s]);
 
+            // We can safely ignore the random vector, since we're working with
+            // relative vectors here... so this question is actually just the same as
+            // the sub question above LOL!
+            let (notice_raw, notice) = locale::pair("mechanics.suvat.t1.relative_notice", &[("r", &formatted_raw_r_s, &formatted_r_s)]);
+            let mut steps = vec![
+                MarkStep::new(notice_raw, notice),
+            ];
+            steps.append(&mut displacement_steps(&formatted_raw_r_s, &formatted_r_s));
+
+            let (header_raw, header) = locale::pair("mechanics.suvat.t1.relative_position", &[
+                ("p", &p.raw, &p.latex),
+                ("r", &formatted_raw_r_s, &formatted_r_s),
+                ("s", &format!("{formatted_raw_s}{LABEL_M_RAW}"), &format!("{formatted_s}{LABEL_M}")),
+            ]);
+            let (body_raw, body) = locale::pair("mechanics.suvat.t1.relative_displacement", &[
+                ("p", &p.raw, &p.latex),
+                ("r", &formatted_raw_r_s, &formatted_r_s),
+                ("t", &t_2, &t_2),
+            ]);
             let rq_1_b = Question::from(
-                QuestionHeader::new(
-                    format!(r#"The position vector of {p_label_raw} relative to {formatted_raw_r_s} is {formatted_raw_s}{LABEL_M_RAW} at T = 0."#),
-                    format!(r#"The position vector of \({p_label}\) relative to \({formatted_r_s}\) is \({formatted_s}{LABEL_M}\) at \(\mathbf{{T}}=0\)."#)
-                ),
-                format!(r#"Find the position vector of {p_label_raw} relative to {formatted_raw_r_s} at time T = {t_2} seconds."#),
-                format!(r#"Find the position vector of \({p_label}\) relative to \({formatted_r_s}\) at time \(\mathbf{{T}}={t_2}\) seconds."#),
-                MarkScheme::from(
-                    format!(r#"Notice how the question is asking for an answer relative to {formatted_raw_r_s}, this means we can ignore it entirely. It would not be the case if it asked for an answer relative to the origin O. Understand the integral of velocity is displacement, s = I(v) + K. Solving for constant, K, at T = 0, and finding a complete equation for s. Finally, substituting T = {t_2} using the found equation for s to get s = {}{LABEL_M_RAW}"#, {
-                        // We can safely ignore the random vector, since we're working with
-                        // relative vectors here... so this question is actually just the same as
-                        // the sub question above LOL!
-                        // v = I(a) + c
-                        // a = I(v) + k
-                        let (ic, jc) = (v_i - a_i * t_0, v_j - a_j * t_0);
-                        let (iv0, ic, is, jv0, jc, js, t2) = (a_i as f32, ic as f32, i_s as f32, a_j as f32, jc as f32, j_s as f32, t_2 as f32);
-                        let (is, js) = (0.5 * iv0 * t2 * t2 + ic * t2 + is, 0.5 * jv0 * t2 * t2 + jc * t2 + js);
-                        formatter::format_f32_group_labelled_raw2(&[is, js])
-                    }),
-                    format!(r#"Notice how the question is asking for an answer relative to \({formatted_r_s}\), this means we can ignore it entirely. It would not be the case if it asked for an answer relative to the origin \(\mathbf{{O}}\). Understand the integral of velocity is displacement, \(\mathbf{{s}}=\int{{\mathbf{{v}}}}\,dt+\mathbf{{k}}\). Solving for constant, \(\mathbf{{k}}\), at \(\mathbf{{T}} = {t_2}\) using the found equation for \(\mathbf{{s}}\) to get \(\mathbf{{s}}={}{LABEL_M}\)"#, {
-                        // We can safely ignore the random vector, since we're working with
-                        // relative vectors here... so this question is actually just the same as
-                        // the sub question above LOL!
-                        // v = I(a) + c
-                        // a = I(v) + k
-                        let (ic, jc) = (v_i - a_i * t_0, v_j - a_j * t_0);
-                        let (iv0, ic, is, jv0, jc, js, t2) = (a_i as f32, ic as f32, i_s as f32, a_j as f32, jc as f32, j_s as f32, t_2 as f32);
-                        let (is, js) = (0.5 * iv0 * t2 * t2 + ic * t2 + is, 0.5 * jv0 * t2 * t2 + jc * t2 + js);
-                        formatter::format_f32_group_labelled_raw(&[is, js])
-                    })
-                )
+                QuestionHeader::new(header_raw, header),
+                body_raw,
+                body,
+                MarkScheme::from_steps(steps)
             );
             stacker.next_root_sub_question(rq_1_b);
         }
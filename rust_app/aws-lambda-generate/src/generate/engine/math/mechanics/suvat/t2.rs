@@ -0,0 +1,189 @@
+/*
+ * 
+ * DAT: 18/10/2026 16:10
+ * DES: Multiple choice version of t1, a particle with constant acceleration and a known velocity.
+ * ASK: Pick the velocity of the particle at T
+ * ASK: Pick the direction of motion of the particle at T
+ *
+ */
+
+use common_types::Generate::GenerateOption;
+use crate::generate::{
+    engine::registry::Template,
+    formatter::{self, LABEL_MS, LABEL_MS_RAW, LABEL_AS, LABEL_AS_RAW},
+    helper,
+    locale,
+    oncelabel::OnceLabel,
+    questionstacker::Stacker,
+    question::{Question, QuestionHeader, MarkScheme, MarkStep, Mark, Choice, Misconception},
+};
+
+pub const TEMPLATE: Template = Template {
+    id: "mechanics.suvat.t2",
+    topic: GenerateOption::SUVAT,
+    spec_code: "9MA0",
+    spec_reference: "7.4",
+    difficulty: 1..=2,
+    expected_marks: 2,
+    authored_on: "18/10/2026 16:10",
+    description: "Multiple choice version of t1, a particle with constant acceleration and a known velocity.",
+    weight: 1,
+    tags: &["kinematics", "constant-acceleration", "multiple-choice"],
+    reserved_labels: &["T"],
+    generator: generate,
+};
+
+fn format_velocity(values: &[i32]) -> (String, String) {
+    (
+        format!(r#"{}{LABEL_MS_RAW}"#, formatter::format_i32_group_labelled_raw2(values)),
+        format!(r#"\({}{LABEL_MS}\)"#, formatter::format_i32_group_labelled_raw(values)),
+    )
+}
+
+fn format_angle(degrees: f32) -> (String, String) {
+    let formatted = formatter::format_f32_raw(degrees, None);
+    (locale::format("unit.degrees", &[("value", &formatted)]), format!(r#"\({formatted}^\circ\)"#))
+}
+
+// Anticlockwise from the i direction, in radians
+fn direction(i: i32, j: i32) -> f32 {
+    (j as f32).atan2(i as f32)
+}
+
+// c = v - at_0, the velocity at t_1 is then at_1 + c
+fn velocity_at(t_0: i32, t_1: i32, (a_i, a_j): (i32, i32), (v_i, v_j): (i32, i32)) -> [i32; 2] {
+    [a_i * t_1 + v_i - a_i * t_0, a_j * t_1 + v_j - a_j * t_0]
+}
+
+fn velocity_distractors(t_0: i32, t_1: i32, (a_i, a_j): (i32, i32), (v_i, v_j): (i32, i32)) -> [([i32; 2], Misconception); 3] {
+    [
+        // c = v + at_0
+        ([a_i * t_1 + v_i + a_i * t_0, a_j * t_1 + v_j + a_j * t_0], Misconception::SignError),
+        ([a_i * t_1, a_j * t_1], Misconception::MissingConstant),
+        // The velocity at t_0, which is just the one given
+        ([v_i, v_j], Misconception::WrongTime),
+    ]
+}
+
+// Angles as shown on the choice, the degree/radian mix up shows the radians. The sign error is left out when
+// the velocity has no j component, flipping the sign of zero wouldn't be a different answer
+fn direction_distractors(t_1: i32, (a_i, a_j): (i32, i32), (v_i, v_j): (i32, i32), [v1_i, v1_j]: [i32; 2]) -> Vec<(f32, Misconception)> {
+    let angle = direction(v1_i, v1_j);
+    let mut distractors = vec![
+        (angle, Misconception::DegreeRadian),
+        (direction(a_i * t_1, a_j * t_1).to_degrees(), Misconception::MissingConstant),
+        (direction(v_i, v_j).to_degrees(), Misconception::WrongTime),
+    ];
+    if v1_j != 0 {
+        distractors.push((-angle.to_degrees(), Misconception::SignError));
+    }
+    distractors
+}
+
+fn all_distinct(texts: &[String]) -> bool {
+    texts.iter().enumerate().all(|(index, text)| !texts[..index].contains(text))
+}
+
+pub fn generate() -> Stacker {
+    let mut stacker = Stacker::new();
+    let mut oncelabel = OnceLabel::for_template(&TEMPLATE);
+
+    let p_object = helper::get_particle_object_name();
+    let p = oncelabel.next_label().expect("a single label is always free");
+    // Both times are at least 1 so the sign error and missing constant can't land on the answer, the rest is
+    // redrawn until every choice reads differently so none of the misconceptions get dropped
+    let (t_0, t_1, a_i, a_j, v_i, v_j) = loop {
+        let t_0 = helper::gen_range_i32(1, 6);
+        let t_1 = helper::gen_range_i32_except(1, 6, t_0);
+        let a = (helper::gen_range_i32_except(-10, 10, 0), helper::gen_range_i32_except(-10, 10, 0));
+        let v = (helper::gen_range_i32_except(-10, 10, 0), helper::gen_range_i32_except(-10, 10, 0));
+
+        let v1 = velocity_at(t_0, t_1, a, v);
+        let mut velocities = vec![format_velocity(&v1).0];
+        velocities.extend(velocity_distractors(t_0, t_1, a, v).iter().map(|(velocity, _)| format_velocity(velocity).0));
+        let mut angles = vec![format_angle(direction(v1[0], v1[1]).to_degrees()).0];
+        angles.extend(direction_distractors(t_1, a, v, v1).iter().map(|(angle, _)| format_angle(*angle).0));
+        // There's no direction question when the particle is at rest
+        let at_rest = v1 == [0, 0];
+        if all_distinct(&velocities) && (at_rest || all_distinct(&angles)) {
+            break (t_0, t_1, a.0, a.1, v.0, v.1);
+        }
+    };
+    stacker.fingerprint_parameters(&[t_0, t_1, a_i, a_j, v_i, v_j]);
+
+    let formatted_a = formatter::format_i32_group_labelled_raw(&[a_i, a_j]);
+    let formatted_v = formatter::format_i32_group_labelled_raw(&[v_i, v_j]);
+
+    let formatted_raw_a = formatter::format_i32_group_labelled_raw2(&[a_i, a_j]);
+    let formatted_raw_v = formatter::format_i32_group_labelled_raw2(&[v_i, v_j]);
+
+    // v = I(a) + c
+    let (c_i, c_j) = (v_i - a_i * t_0, v_j - a_j * t_0);
+    let [v1_i, v1_j] = velocity_at(t_0, t_1, (a_i, a_j), (v_i, v_j));
+
+    // (1) Root question body
+    let (rq_1_raw, rq_1_latex) = locale::pair("mechanics.suvat.particle", &[
+        ("object", &p_object, &p_object),
+        ("p", &p.raw, &p.latex),
+        ("a", &format!("{formatted_raw_a}{LABEL_AS_RAW}"), &format!("{formatted_a}{LABEL_AS}")),
+        ("t0", &t_0, &t_0),
+        ("v", &format!("{formatted_raw_v}{LABEL_MS_RAW}"), &format!("{formatted_v}{LABEL_MS}")),
+    ]);
+    let rq_1 = Question::new(QuestionHeader::new(rq_1_raw, rq_1_latex));
+    stacker.next_root_question(rq_1);
+
+    // (1.a) Velocity at t_1
+    let (correct_raw, correct) = format_velocity(&[v1_i, v1_j]);
+    let (rq_1_a_raw, rq_1_a_latex) = locale::pair("mechanics.suvat.t2.velocity", &[("p", &p.raw, &p.latex), ("t", &t_1, &t_1)]);
+    let (constant_raw, constant) = locale::pair("mechanics.suvat.t2.velocity_constant", &[("t0", &t_0, &t_0)]);
+    let (substitute_raw, substitute) = locale::pair("mechanics.suvat.substitute_velocity", &[("t", &t_1, &t_1)]);
+    let rq_1_a = Question::from_header_and_scheme(
+        QuestionHeader::new(rq_1_a_raw, rq_1_a_latex),
+        MarkScheme::from_steps(vec![
+            MarkStep::new(constant_raw, constant).result(
+                format!(r#"C = {}"#, formatter::format_i32_group_labelled_raw2(&[c_i, c_j])),
+                format!(r#"\(\mathbf{{c}}={}\)"#, formatter::format_i32_group_labelled_raw(&[c_i, c_j])),
+            ),
+            MarkStep::new(substitute_raw, substitute).result(
+                format!(r#"v = {correct_raw}"#),
+                format!(r#"\(\mathbf{{v}}=\){correct}"#),
+            ).mark(Mark::B1),
+        ])
+    );
+    let choices = helper::shuffle_choices(
+        Choice::correct(correct_raw, correct),
+        velocity_distractors(t_0, t_1, (a_i, a_j), (v_i, v_j)).into_iter()
+            .map(|(velocity, misconception)| {
+                let (raw, latex) = format_velocity(&velocity);
+                Choice::distractor(raw, latex, misconception)
+            })
+            .collect()
+    );
+    stacker.next_root_sub_multiple_choice(rq_1_a, choices);
+
+    // (1.b) Direction of motion at t_1, there isn't one if the particle is at rest
+    if v1_i != 0 || v1_j != 0 {
+        let angle = direction(v1_i, v1_j);
+        let (correct_raw, correct) = format_angle(angle.to_degrees());
+        let (rq_1_b_raw, rq_1_b_latex) = locale::pair("mechanics.suvat.t2.direction", &[("p", &p.raw, &p.latex), ("t", &t_1, &t_1)]);
+        let (angle_raw, angle_latex) = locale::pair("mechanics.suvat.t2.direction_angle", &[]);
+        let rq_1_b = Question::from_header_and_scheme(
+            QuestionHeader::new(rq_1_b_raw, rq_1_b_latex),
+            MarkScheme::from_steps(vec![
+                MarkStep::new(angle_raw, angle_latex).result(
+                    correct_raw.clone(),
+                    correct.clone(),
+                ).mark(Mark::B1),
+            ])
+        );
+        let distractors = direction_distractors(t_1, (a_i, a_j), (v_i, v_j), [v1_i, v1_j]).into_iter()
+            .map(|(angle, misconception)| {
+                let (raw, latex) = format_angle(angle);
+                Choice::distractor(raw, latex, misconception)
+            })
+            .collect();
+        stacker.next_root_sub_multiple_choice(rq_1_b, helper::shuffle_choices(Choice::correct(correct_raw, correct), distractors));
+    }
+
+    stacker
+}
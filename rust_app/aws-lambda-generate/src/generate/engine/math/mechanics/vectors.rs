@@ -1,10 +1,5 @@
-use rand::seq::SliceRandom;
-use crate::generate::questionstacker::Stacker;
+use crate::generate::engine::registry::Template;
 
 pub mod t1;
 
-static GENERATORS: [fn() -> Stacker; 1] = [t1::generate];
-
-pub fn generate() -> Stacker {
-    GENERATORS.choose(&mut rand::thread_rng()).unwrap()()
-}
+pub static TEMPLATES: [&Template; 1] = [&t1::TEMPLATE];
@@ -1 +1,20 @@
-pub use super::super::suvat::t1::*;
+// Shares its body with suvat::t1, the particle moves in two dimensions so it doubles as a vectors question
+use common_types::Generate::GenerateOption;
+use crate::generate::engine::registry::Template;
+
+pub use super::super::suvat::t1::generate;
+
+pub const TEMPLATE: Template = Template {
+    id: "mechanics.vectors.t1",
+    topic: GenerateOption::Vectors,
+    spec_code: "9MA0",
+    spec_reference: "7.4",
+    difficulty: 2..=3,
+    expected_marks: 6,
+    authored_on: "25/05/2024 11:35",
+    description: "This question models an object as a particle with constant acceleration and a velocity.",
+    weight: 1,
+    tags: &["kinematics", "constant-acceleration", "vectors"],
+    reserved_labels: super::super::suvat::t1::TEMPLATE.reserved_labels,
+    generator: generate,
+};
@@ -0,0 +1,164 @@
+/*
+ * Picks the questions for a personalised paper from the user's marked answers.
+ *
+ * Every topic gets a mastery between 0 and 1, the marks they got over the marks available with
+ * older answers counting for less. Weak topics come up more often, topics they haven't tried yet
+ * come up as often as an average topic, and mastered topics only come back once they are due
+ * again (spaced repetition, the gap doubling each time they get it right).
+ */
+
+use ::std::cmp::Reverse;
+use ::std::collections::HashMap;
+use chrono::NaiveDateTime;
+use rand::seq::SliceRandom;
+use common_types::Generate::GenerateOption;
+
+use crate::generate::helper;
+use super::registry::{self, Template};
+
+// An answer this many days old counts half as much as one made today
+const HALF_LIFE_DAYS: f32 = 30.0;
+const MASTERED: f32 = 0.7;
+const MAX_INTERVAL_DAYS: i64 = 60;
+const NOT_DUE_WEIGHT: f32 = 0.1;
+
+#[derive(Debug, Clone)]
+pub struct MarkedAnswer {
+    pub template_id: String,
+    pub topic: GenerateOption,
+    pub awarded: u32,
+    pub available: u32,
+    pub answered_on: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mastery {
+    pub score: f32,
+    // Days before a mastered topic is worth asking about again
+    pub interval_days: i64,
+    pub last_answered: NaiveDateTime,
+}
+
+impl Mastery {
+    pub fn is_mastered(&self) -> bool {
+        self.score >= MASTERED
+    }
+    pub fn is_due(&self, now: NaiveDateTime) -> bool {
+        (now - self.last_answered).num_days() >= self.interval_days
+    }
+    // Harder questions as the user gets better at a topic
+    pub fn difficulty(&self) -> u8 {
+        match self.score {
+            score if score < 0.4 => 1,
+            score if score < MASTERED => 2,
+            _ => 3,
+        }
+    }
+}
+
+fn decay(answered_on: NaiveDateTime, now: NaiveDateTime) -> f32 {
+    let age_days = (now - answered_on).num_hours().max(0) as f32 / 24.0;
+    0.5f32.powf(age_days / HALF_LIFE_DAYS)
+}
+
+fn mastery_of<'a>(answers: impl Iterator<Item = &'a MarkedAnswer>, now: NaiveDateTime) -> Option<Mastery> {
+    let mut answers = answers.filter(|answer| answer.available > 0).collect::<Vec<&MarkedAnswer>>();
+    let last_answered = answers.iter().map(|answer| answer.answered_on).max()?;
+    let (awarded, available) = answers.iter().fold((0.0, 0.0), |(awarded, available), answer| {
+        let weight = decay(answer.answered_on, now);
+        (awarded + weight * answer.awarded as f32, available + weight * answer.available as f32)
+    });
+
+    // Each answer in a row at or above the mastered mark (newest first) doubles the interval
+    answers.sort_by_key(|answer| Reverse(answer.answered_on));
+    let streak = answers.iter()
+                    .take_while(|answer| answer.awarded as f32 / answer.available as f32 >= MASTERED)
+                    .count()
+                    .min(6) as u32;
+    Some(Mastery {
+        score: if available > 0.0 { awarded / available } else { 0.0 },
+        interval_days: 2i64.pow(streak).min(MAX_INTERVAL_DAYS),
+        last_answered,
+    })
+}
+
+pub fn topic_mastery(history: &[MarkedAnswer], now: NaiveDateTime) -> HashMap<GenerateOption, Mastery> {
+    let mut by_topic: HashMap<&GenerateOption, Vec<&MarkedAnswer>> = HashMap::new();
+    for answer in history.iter() {
+        by_topic.entry(&answer.topic).or_default().push(answer);
+    }
+    by_topic.into_iter()
+        .filter_map(|(topic, answers)| mastery_of(answers.into_iter(), now).map(|mastery| (topic.clone(), mastery)))
+        .collect()
+}
+
+pub fn template_mastery(history: &[MarkedAnswer], now: NaiveDateTime) -> HashMap<String, Mastery> {
+    let mut by_template: HashMap<&str, Vec<&MarkedAnswer>> = HashMap::new();
+    for answer in history.iter() {
+        by_template.entry(&answer.template_id).or_default().push(answer);
+    }
+    by_template.into_iter()
+        .filter_map(|(template_id, answers)| mastery_of(answers.into_iter(), now).map(|mastery| (template_id.to_owned(), mastery)))
+        .collect()
+}
+
+fn weakness_weight(mastery: Option<&Mastery>, now: NaiveDateTime) -> f32 {
+    match mastery {
+        None => 1.0,
+        Some(mastery) if mastery.is_mastered() => if mastery.is_due(now) { 1.0 } else { NOT_DUE_WEIGHT },
+        Some(mastery) => 1.0 + 3.0 * (1.0 - mastery.score),
+    }
+}
+
+// Every topic that has at least one template registered, in registry order
+pub fn available_topics() -> Vec<GenerateOption> {
+    let mut topics: Vec<GenerateOption> = Vec::new();
+    for template in registry::templates() {
+        if !topics.contains(&template.topic) {
+            topics.push(template.topic.clone());
+        }
+    }
+    topics
+}
+
+pub struct Planner {
+    now: NaiveDateTime,
+    topics: HashMap<GenerateOption, Mastery>,
+    templates: HashMap<String, Mastery>,
+}
+
+impl Planner {
+    pub fn new(history: &[MarkedAnswer], now: NaiveDateTime) -> Self {
+        Self {
+            now,
+            topics: topic_mastery(history, now),
+            templates: template_mastery(history, now),
+        }
+    }
+
+    // The topic for each question on the paper, sampled by weakness
+    pub fn topics(&self, length: usize) -> Vec<GenerateOption> {
+        let candidates = available_topics();
+        (0..length).filter_map(|_| helper::with_rng(|rng| {
+            candidates.choose_weighted(rng, |topic| weakness_weight(self.topics.get(topic), self.now)).ok().cloned()
+        })).collect()
+    }
+
+    // Templates at the right difficulty for the user, favouring the ones they have done badly on.
+    // The difficulty is None when the topic has nothing at that difficulty and any template is used
+    pub fn choose_template(&self, topic: &GenerateOption) -> Option<(&'static Template, Option<u8>)> {
+        let difficulty = self.topics.get(topic).map(Mastery::difficulty).unwrap_or(1);
+        let mut candidates = registry::for_option(topic)
+                                .filter(|template| template.difficulty.contains(&difficulty))
+                                .collect::<Vec<&'static Template>>();
+        let mut difficulty = Some(difficulty);
+        if candidates.is_empty() {
+            candidates = registry::for_option(topic).collect();
+            difficulty = None;
+        }
+        let template = helper::with_rng(|rng| candidates.choose_weighted(rng, |template| {
+            template.weight as f32 * weakness_weight(self.templates.get(template.id), self.now)
+        }).ok().copied())?;
+        Some((template, difficulty))
+    }
+}
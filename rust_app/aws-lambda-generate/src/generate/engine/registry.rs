@@ -0,0 +1,53 @@
+use ::std::ops::RangeInclusive;
+use rand::seq::SliceRandom;
+use serde::Serialize;
+use common_types::Generate::GenerateOption;
+
+use crate::generate::{helper, questionstacker::Stacker};
+use super::math;
+
+// Everything we know about a question template, the DAT/DES fields from the header
+// comment of each template live here so they can be queried
+#[derive(Serialize, Debug)]
+pub struct Template {
+    pub id: &'static str,
+    pub topic: GenerateOption,
+    // Exam board specification the template is written against, spec_reference is a section of it
+    pub spec_code: &'static str,
+    pub spec_reference: &'static str,
+    pub difficulty: RangeInclusive<u8>,
+    pub expected_marks: u8,
+    pub authored_on: &'static str,
+    pub description: &'static str,
+    // Relative chance of this template being picked against others with the same topic
+    pub weight: u32,
+    pub tags: &'static [&'static str],
+    // Symbols the question already gives a meaning to (T for time, g, O for origin), never handed out as labels
+    pub reserved_labels: &'static [&'static str],
+    #[serde(skip)]
+    pub generator: fn() -> Stacker,
+}
+
+pub fn templates() -> impl Iterator<Item = &'static Template> {
+    math::mechanics::templates()
+        .chain(math::pure::templates())
+        .chain(math::statistics::templates())
+}
+
+pub fn find(id: &str) -> Option<&'static Template> {
+    templates().find(|template| template.id == id)
+}
+
+pub fn for_option(option: &GenerateOption) -> impl Iterator<Item = &'static Template> + '_ {
+    templates().filter(move |template| &template.topic == option)
+}
+
+pub fn choose(option: &GenerateOption, difficulty: Option<u8>) -> Option<&'static Template> {
+    let candidates = for_option(option)
+                        .filter(|template| match difficulty {
+                            Some(difficulty) => template.difficulty.contains(&difficulty),
+                            None => true,
+                        })
+                        .collect::<Vec<&'static Template>>();
+    helper::with_rng(|rng| candidates.choose_weighted(rng, |template| template.weight).ok().copied())
+}
@@ -0,0 +1,82 @@
+use super::question::{Choice, Question, QuestionHeader, QuestionType};
+
+pub mod html;
+pub mod latex;
+pub mod markdown;
+
+// Flattened view of a paper in the order it's printed, each part carries the number
+// it gets from where it sits in the Grouped nesting
+pub struct Entry<'a> {
+    // Full number, e.g. 1(a)(i)
+    pub number: String,
+    // Just this level of the number, e.g. (i)
+    pub part: String,
+    pub depth: usize,
+    pub kind: EntryKind<'a>,
+}
+
+pub enum EntryKind<'a> {
+    Header(&'a QuestionHeader),
+    Question(&'a Question),
+    MultipleChoice(&'a Question, &'a [Choice]),
+}
+
+pub fn entries(questions: &[QuestionType]) -> Vec<Entry<'_>> {
+    let mut entries = Vec::new();
+    push_entries(&mut entries, questions, "", 0);
+    entries
+}
+
+fn push_entries<'a>(entries: &mut Vec<Entry<'a>>, questions: &'a [QuestionType], prefix: &str, depth: usize) {
+    for (index, question) in questions.iter().enumerate() {
+        let part = part_label(depth, index);
+        let number = format!("{prefix}{part}");
+        match question {
+            QuestionType::Single(question) => entries.push(Entry { number, part, depth, kind: EntryKind::Question(question) }),
+            QuestionType::MultipleChoice(question, choices) => entries.push(Entry { number, part, depth, kind: EntryKind::MultipleChoice(question, choices) }),
+            QuestionType::Grouped(header, questions) => {
+                entries.push(Entry { number: number.clone(), part, depth, kind: EntryKind::Header(header) });
+                push_entries(entries, questions, &number, depth + 1);
+            },
+        }
+    }
+}
+
+// Header and body of a question as they're printed together, both in LaTeX
+fn question_text(question: &Question) -> String {
+    [question.header.latex_text.as_str(), question.latex_text.as_str()]
+        .into_iter()
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// 1, (a), (i) and then (A) for anything nested deeper than exam papers go
+pub fn part_label(depth: usize, index: usize) -> String {
+    match depth {
+        0 => (index + 1).to_string(),
+        1 => format!("({})", letter(index).to_ascii_lowercase()),
+        2 => format!("({})", roman(index + 1)),
+        _ => format!("({})", letter(index)),
+    }
+}
+
+pub fn choice_label(index: usize) -> char {
+    letter(index)
+}
+
+fn letter(index: usize) -> char {
+    (b'A' + (index % 26) as u8) as char
+}
+
+fn roman(mut value: usize) -> String {
+    const NUMERALS: [(usize, &str); 9] = [(100, "c"), (90, "xc"), (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i")];
+    let mut result = String::new();
+    for (amount, numeral) in NUMERALS {
+        while value >= amount {
+            result.push_str(numeral);
+            value -= amount;
+        }
+    }
+    result
+}
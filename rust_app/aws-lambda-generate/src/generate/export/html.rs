@@ -0,0 +1,123 @@
+use ::std::fmt::Write;
+use common_types::Generate::Locale;
+
+use crate::generate::{
+    diagram::Diagram,
+    paper::Paper,
+    question::{Choice, MarkScheme},
+};
+use super::{choice_label, entries, question_text, EntryKind};
+
+const HEAD: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<script>window.MathJax = { tex: { inlineMath: [['\\(', '\\)']] } };</script>
+<script id="MathJax-script" async src="https://cdn.jsdelivr.net/npm/mathjax@3/es5/tex-mml-chtml.js"></script>
+<style>
+body { font-family: sans-serif; max-width: 50em; margin: 2em auto; padding: 0 1em; line-height: 1.5; }
+.part { display: flex; gap: 1em; }
+.part .label { font-weight: bold; min-width: 2em; }
+.part .body { flex: 1; }
+.marks { float: right; font-weight: bold; }
+ol.choices { list-style-type: upper-alpha; }
+details { margin: 0.5em 0 1em; padding: 0.5em 1em; background: #f4f4f4; }
+summary { cursor: pointer; }
+.mark { float: right; font-weight: bold; }
+</style>
+"#;
+
+pub fn render(paper: &Paper) -> String {
+    let options = paper.generated_options().iter().map(ToString::to_string).collect::<Vec<String>>().join(", ");
+    let mut document = String::from(HEAD);
+    writeln!(document, "<title>{} Practice Paper</title>\n</head>\n<body>", paper.category_name()).expect("html export failed to write to string");
+    writeln!(document, "<h1>{} Practice Paper</h1>", paper.category_name()).expect("html export failed to write to string");
+    writeln!(document, "<p>{} &middot; {}<br>Total marks: {} &middot; Suggested time: {} minutes</p>", escape(&options), paper.created_on().format("%d/%m/%Y"), paper.total_marks(), paper.suggested_minutes()).expect("html export failed to write to string");
+
+    for entry in entries(paper.questions()) {
+        let indent = entry.depth * 2;
+        match entry.kind {
+            EntryKind::Header(header) => {
+                writeln!(document, r#"<div class="part" style="margin-left: {indent}em"><span class="label">{}</span><div class="body">{}{}</div></div>"#, entry.part, escape(&header.latex_text), figure(&header.diagram)).expect("html export failed to write to string");
+            },
+            EntryKind::Question(question) => {
+                writeln!(document, r#"<div class="part" style="margin-left: {indent}em"><span class="label">{}</span><div class="body"><span class="marks">[{}]</span>{}{}"#, entry.part, question.marks, escape(&question_text(question)), figure(&question.header.diagram)).expect("html export failed to write to string");
+                if !question.mark_scheme.is_empty() {
+                    write_mark_scheme(&mut document, &entry.number, &question.mark_scheme, None, paper.locale());
+                }
+                document.push_str("</div></div>\n");
+            },
+            EntryKind::MultipleChoice(question, choices) => {
+                writeln!(document, r#"<div class="part" style="margin-left: {indent}em"><span class="label">{}</span><div class="body"><span class="marks">[{}]</span>{}{}"#, entry.part, question.marks, escape(&question_text(question)), figure(&question.header.diagram)).expect("html export failed to write to string");
+                document.push_str("<ol class=\"choices\">\n");
+                for choice in choices.iter() {
+                    writeln!(document, "<li>{}</li>", escape(&choice.latex_text)).expect("html export failed to write to string");
+                }
+                document.push_str("</ol>\n");
+                write_mark_scheme(&mut document, &entry.number, &question.mark_scheme, Some(choices), paper.locale());
+                document.push_str("</div></div>\n");
+            },
+        }
+    }
+
+    document.push_str("</body>\n</html>\n");
+    document
+}
+
+fn figure(diagram: &Option<Diagram>) -> String {
+    match diagram {
+        Some(diagram) => format!(r#"<figure class="diagram">{}</figure>"#, diagram.render()),
+        None => String::new(),
+    }
+}
+
+// Collapsed by default so the paper can be attempted before looking at the answers
+fn write_mark_scheme(document: &mut String, number: &str, mark_scheme: &MarkScheme, choices: Option<&[Choice]>, locale: Locale) {
+    writeln!(document, "<details><summary>Mark scheme for {number}</summary>").expect("html export failed to write to string");
+    if let Some(choices) = choices {
+        document.push_str("<ul>\n");
+        for (index, choice) in choices.iter().enumerate() {
+            match choice.misconception {
+                None => writeln!(document, "<li><b>{}</b> is correct</li>", choice_label(index)).expect("html export failed to write to string"),
+                Some(misconception) => writeln!(document, "<li><b>{}</b> {}</li>", choice_label(index), escape(misconception.feedback(locale))).expect("html export failed to write to string"),
+            }
+        }
+        document.push_str("</ul>\n");
+    }
+    if mark_scheme.steps.is_empty() {
+        writeln!(document, "<p>{}</p>", escape(&mark_scheme.latex_text)).expect("html export failed to write to string");
+    } else {
+        document.push_str("<ol>\n");
+        for step in mark_scheme.steps.iter() {
+            document.push_str("<li>");
+            if let Some(mark) = step.mark {
+                write!(document, r#"<span class="mark">{mark}</span>"#).expect("html export failed to write to string");
+            }
+            document.push_str(&escape(&step.explanation.latex_text));
+            for text in [&step.working, &step.result] {
+                if !text.is_empty() {
+                    write!(document, "<br>{}", escape(&text.latex_text)).expect("html export failed to write to string");
+                }
+            }
+            document.push_str("</li>\n");
+        }
+        document.push_str("</ol>\n");
+    }
+    document.push_str("</details>\n");
+}
+
+// MathJax reads the text content so escaping inside the math delimiters is fine
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,130 @@
+use ::std::fmt::Write;
+use common_types::Generate::Locale;
+
+use crate::generate::{
+    paper::Paper,
+    question::{Choice, MarkScheme},
+};
+use super::{choice_label, entries, question_text, EntryKind};
+
+// Fragments are written for MathJax, \unicode only exists there so it's mapped onto pifont
+const PREAMBLE: &str = r#"\documentclass[11pt,a4paper]{article}
+\usepackage[margin=2cm]{geometry}
+\usepackage{amsmath}
+\usepackage{amssymb}
+\usepackage{pifont}
+\setlength{\parindent}{0pt}
+\expandafter\newcommand\csname rapidlunicodex2714\endcsname{\ding{51}}
+\expandafter\newcommand\csname rapidlunicodex2718\endcsname{\ding{55}}
+\newcommand{\unicode}[1]{\text{\csname rapidlunicode#1\endcsname}}
+"#;
+
+pub struct LatexExport {
+    pub question_paper: String,
+    pub mark_scheme: String,
+}
+
+pub fn render(paper: &Paper) -> LatexExport {
+    LatexExport {
+        question_paper: render_question_paper(paper),
+        mark_scheme: render_mark_scheme(paper),
+    }
+}
+
+// Diagrams are SVG only and pdflatex can't include those without converting them first, so they're left out
+pub fn render_question_paper(paper: &Paper) -> String {
+    let mut document = begin_document(paper, "");
+    writeln!(document, r#"\textbf{{Total marks: {}}} \hfill \textbf{{Suggested time: {} minutes}}"#, paper.total_marks(), paper.suggested_minutes()).expect("latex export failed to write to string");
+    document.push_str("\\bigskip\n\n");
+
+    for entry in entries(paper.questions()) {
+        let indent = entry.depth as f32 * 2.5;
+        match entry.kind {
+            EntryKind::Header(header) => {
+                writeln!(document, r#"\hangindent={}em\hspace*{{{}em}}\makebox[2.5em][l]{{\textbf{{{}}}}}{}\par\medskip"#, indent + 2.5, indent, entry.part, header.latex_text).expect("latex export failed to write to string");
+            },
+            EntryKind::Question(question) => {
+                writeln!(document, r#"\hangindent={}em\hspace*{{{}em}}\makebox[2.5em][l]{{\textbf{{{}}}}}{}\hfill\textbf{{[{}]}}\par\medskip"#, indent + 2.5, indent, entry.part, question_text(question), question.marks).expect("latex export failed to write to string");
+            },
+            EntryKind::MultipleChoice(question, choices) => {
+                writeln!(document, r#"\hangindent={}em\hspace*{{{}em}}\makebox[2.5em][l]{{\textbf{{{}}}}}{}\hfill\textbf{{[{}]}}\par\smallskip"#, indent + 2.5, indent, entry.part, question_text(question), question.marks).expect("latex export failed to write to string");
+                for (index, choice) in choices.iter().enumerate() {
+                    writeln!(document, r#"\hspace*{{{}em}}\makebox[2em][l]{{\textbf{{{}}}}}{}\par"#, indent + 2.5, choice_label(index), choice.latex_text).expect("latex export failed to write to string");
+                }
+                document.push_str("\\medskip\n");
+            },
+        }
+    }
+
+    end_document(document)
+}
+
+pub fn render_mark_scheme(paper: &Paper) -> String {
+    let mut document = begin_document(paper, " -- Mark Scheme");
+
+    for entry in entries(paper.questions()) {
+        match entry.kind {
+            EntryKind::Header(_) => (),
+            EntryKind::Question(question) => {
+                if question.mark_scheme.is_empty() {
+                    continue;
+                }
+                writeln!(document, r#"\textbf{{{}}}\hfill\textbf{{[{}]}}\par"#, entry.number, question.marks).expect("latex export failed to write to string");
+                write_mark_scheme(&mut document, &question.mark_scheme);
+            },
+            EntryKind::MultipleChoice(question, choices) => {
+                writeln!(document, r#"\textbf{{{}}}\hfill\textbf{{[{}]}}\par"#, entry.number, question.marks).expect("latex export failed to write to string");
+                write_choices(&mut document, choices, paper.locale());
+                write_mark_scheme(&mut document, &question.mark_scheme);
+            },
+        }
+    }
+
+    end_document(document)
+}
+
+fn begin_document(paper: &Paper, title_suffix: &str) -> String {
+    let options = paper.generated_options().iter().map(ToString::to_string).collect::<Vec<String>>().join(", ");
+    let mut document = String::from(PREAMBLE);
+    document.push_str("\\begin{document}\n");
+    writeln!(document, r#"{{\Large\textbf{{{} Practice Paper{}}}}}\par"#, paper.category_name(), title_suffix).expect("latex export failed to write to string");
+    writeln!(document, r#"{}\hfill {}\par\medskip"#, options, paper.created_on().format("%d/%m/%Y")).expect("latex export failed to write to string");
+    document
+}
+
+fn end_document(mut document: String) -> String {
+    document.push_str("\\end{document}\n");
+    document
+}
+
+fn write_mark_scheme(document: &mut String, mark_scheme: &MarkScheme) {
+    if mark_scheme.steps.is_empty() {
+        writeln!(document, "{}\\par\\medskip", mark_scheme.latex_text).expect("latex export failed to write to string");
+        return;
+    }
+    document.push_str("\\begin{enumerate}\n");
+    for step in mark_scheme.steps.iter() {
+        write!(document, r#"\item {}"#, step.explanation.latex_text).expect("latex export failed to write to string");
+        for text in [&step.working, &step.result] {
+            if !text.is_empty() {
+                write!(document, r#"\\ {}"#, text.latex_text).expect("latex export failed to write to string");
+            }
+        }
+        if let Some(mark) = step.mark {
+            write!(document, r#"\hfill\textbf{{{mark}}}"#).expect("latex export failed to write to string");
+        }
+        document.push('\n');
+    }
+    document.push_str("\\end{enumerate}\n\\medskip\n");
+}
+
+fn write_choices(document: &mut String, choices: &[Choice], locale: Locale) {
+    document.push_str("\\begin{itemize}\n");
+    for (index, choice) in choices.iter().enumerate() {
+        match choice.misconception {
+            None => writeln!(document, r#"\item[\textbf{{{}}}] {} \hfill Correct"#, choice_label(index), choice.latex_text).expect("latex export failed to write to string"),
+            Some(misconception) => writeln!(document, r#"\item[\textbf{{{}}}] {} -- {}"#, choice_label(index), choice.latex_text, misconception.feedback(locale)).expect("latex export failed to write to string"),
+        }
+    }
+    document.push_str("\\end{itemize}\n");
+}
@@ -0,0 +1,104 @@
+use ::std::fmt::Write;
+use common_types::Generate::Locale;
+
+use crate::generate::{
+    diagram::Diagram,
+    paper::Paper,
+    question::{Choice, MarkScheme},
+};
+use super::{choice_label, entries, question_text, EntryKind};
+
+pub fn render(paper: &Paper) -> String {
+    let options = paper.generated_options().iter().map(ToString::to_string).collect::<Vec<String>>().join(", ");
+    let mut document = String::new();
+    writeln!(document, "# {} Practice Paper\n", paper.category_name()).expect("markdown export failed to write to string");
+    writeln!(document, "{} · {}  \nTotal marks: {} · Suggested time: {} minutes\n", options, paper.created_on().format("%d/%m/%Y"), paper.total_marks(), paper.suggested_minutes()).expect("markdown export failed to write to string");
+
+    // Markdown has no good way to indent, so parts use their full number instead
+    for entry in entries(paper.questions()) {
+        match entry.kind {
+            EntryKind::Header(header) => {
+                writeln!(document, "**{}** {}\n", entry.number, math(&header.latex_text)).expect("markdown export failed to write to string");
+                write_diagram(&mut document, &header.diagram);
+            },
+            EntryKind::Question(question) => {
+                writeln!(document, "**{}** {} **[{}]**\n", entry.number, math(&question_text(question)), question.marks).expect("markdown export failed to write to string");
+                write_diagram(&mut document, &question.header.diagram);
+            },
+            EntryKind::MultipleChoice(question, choices) => {
+                writeln!(document, "**{}** {} **[{}]**\n", entry.number, math(&question_text(question)), question.marks).expect("markdown export failed to write to string");
+                write_diagram(&mut document, &question.header.diagram);
+                for (index, choice) in choices.iter().enumerate() {
+                    writeln!(document, "- **{}** {}", choice_label(index), math(&choice.latex_text)).expect("markdown export failed to write to string");
+                }
+                document.push('\n');
+            },
+        }
+    }
+
+    document.push_str("---\n\n## Mark Scheme\n\n");
+    for entry in entries(paper.questions()) {
+        match entry.kind {
+            EntryKind::Header(_) => (),
+            EntryKind::Question(question) => {
+                if question.mark_scheme.is_empty() {
+                    continue;
+                }
+                writeln!(document, "### {} [{}]\n", entry.number, question.marks).expect("markdown export failed to write to string");
+                write_mark_scheme(&mut document, &question.mark_scheme);
+            },
+            EntryKind::MultipleChoice(question, choices) => {
+                writeln!(document, "### {} [{}]\n", entry.number, question.marks).expect("markdown export failed to write to string");
+                write_choices(&mut document, choices, paper.locale());
+                write_mark_scheme(&mut document, &question.mark_scheme);
+            },
+        }
+    }
+
+    document
+}
+
+// Most renderers pass inline HTML through, so the SVG goes in as is
+fn write_diagram(document: &mut String, diagram: &Option<Diagram>) {
+    if let Some(diagram) = diagram {
+        writeln!(document, "{}\n", diagram.render()).expect("markdown export failed to write to string");
+    }
+}
+
+fn write_mark_scheme(document: &mut String, mark_scheme: &MarkScheme) {
+    if mark_scheme.steps.is_empty() {
+        writeln!(document, "{}\n", math(&mark_scheme.latex_text)).expect("markdown export failed to write to string");
+        return;
+    }
+    for (index, step) in mark_scheme.steps.iter().enumerate() {
+        write!(document, "{}. {}", index + 1, math(&step.explanation.latex_text)).expect("markdown export failed to write to string");
+        for text in [&step.working, &step.result] {
+            if !text.is_empty() {
+                write!(document, "  \n   {}", math(&text.latex_text)).expect("markdown export failed to write to string");
+            }
+        }
+        if let Some(mark) = step.mark {
+            write!(document, " **{mark}**").expect("markdown export failed to write to string");
+        }
+        document.push('\n');
+    }
+    document.push('\n');
+}
+
+fn write_choices(document: &mut String, choices: &[Choice], locale: Locale) {
+    for (index, choice) in choices.iter().enumerate() {
+        match choice.misconception {
+            None => writeln!(document, "- **{}** is correct", choice_label(index)).expect("markdown export failed to write to string"),
+            Some(misconception) => writeln!(document, "- **{}** {}", choice_label(index), misconception.feedback(locale)).expect("markdown export failed to write to string"),
+        }
+    }
+    document.push('\n');
+}
+
+// Fragments use \( \) and \[ \] for MathJax, most markdown renderers want dollars
+fn math(text: &str) -> String {
+    text.replace(r#"\("#, "$")
+        .replace(r#"\)"#, "$")
+        .replace(r#"\["#, "$$")
+        .replace(r#"\]"#, "$$")
+}
@@ -1,6 +1,8 @@
 use ::std::fmt::Write; 
 use rand::seq::SliceRandom;
 
+use super::helper;
+
 pub const LABEL_KMH: &'static str = r#"\text{kmh}^{-1}"#;
 pub const LABEL_KMS: &'static str = r#"\text{kms}^{-1}"#;
 pub const LABEL_MH: &'static str = r#"\text{mh}^{-1}"#;
@@ -27,48 +29,47 @@ pub const LABEL_AH_RAW: &'static str = "mh^-2";
 pub const LABEL_M_RAW: &'static str = r#"m"#;
 pub const LABEL_KM_RAW: &'static str = r#"km"#;
 
-pub const LABELLED_SYMBOLS: [&'static str; 10] = [
-    r#"\alpha"#,
-    r#"\beta"#,
-    r#"\gamma"#,
-    r#"\delta"#,
-    r#"\epsilon"#,
-    r#"\zeta"#,
-    r#"\eta"#,
-    r#"\theta"#,
-    r#"\iota"#,
-    r#"\kappa"#,
-];
-pub const LABELLED_IDENTIFIERS_RAW: [&'static str; 25] = [
-    "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", 
-    "N", "O", "P", "Q", "R", "S", "U", "V", "W", "X", "Y", "Z"
+// Raw and LaTeX forms of each symbol, kept together so they can never drift apart
+pub const LABELLED_SYMBOLS: [(&'static str, &'static str); 10] = [
+    ("α", r#"\alpha"#),
+    ("β", r#"\beta"#),
+    ("γ", r#"\gamma"#),
+    ("δ", r#"\delta"#),
+    ("ε", r#"\epsilon"#),
+    ("ζ", r#"\zeta"#),
+    ("η", r#"\eta"#),
+    ("θ", r#"\theta"#),
+    ("ι", r#"\iota"#),
+    ("κ", r#"\kappa"#),
 ];
-pub const LABELLED_IDENTIFIERS: [&'static str; 25] = [
-    r#"\mathbf{A}"#,
-    r#"\mathbf{B}"#,
-    r#"\mathbf{C}"#,
-    r#"\mathbf{D}"#,
-    r#"\mathbf{E}"#,
-    r#"\mathbf{F}"#,
-    r#"\mathbf{G}"#,
-    r#"\mathbf{H}"#,
-    r#"\mathbf{I}"#,
-    r#"\mathbf{J}"#,
-    r#"\mathbf{K}"#,
-    r#"\mathbf{L}"#,
-    r#"\mathbf{M}"#,
-    r#"\mathbf{N}"#,
-    r#"\mathbf{O}"#,
-    r#"\mathbf{P}"#,
-    r#"\mathbf{Q}"#,
-    r#"\mathbf{R}"#,
-    r#"\mathbf{S}"#,
-    r#"\mathbf{U}"#,
-    r#"\mathbf{V}"#,
-    r#"\mathbf{W}"#,
-    r#"\mathbf{X}"#,
-    r#"\mathbf{Y}"#,
-    r#"\mathbf{Z}"#,
+// Every capital letter, templates reserve the ones that already mean something (T for time, O for origin)
+pub const LABELLED_IDENTIFIERS: [(&'static str, &'static str); 26] = [
+    ("A", r#"\mathbf{A}"#),
+    ("B", r#"\mathbf{B}"#),
+    ("C", r#"\mathbf{C}"#),
+    ("D", r#"\mathbf{D}"#),
+    ("E", r#"\mathbf{E}"#),
+    ("F", r#"\mathbf{F}"#),
+    ("G", r#"\mathbf{G}"#),
+    ("H", r#"\mathbf{H}"#),
+    ("I", r#"\mathbf{I}"#),
+    ("J", r#"\mathbf{J}"#),
+    ("K", r#"\mathbf{K}"#),
+    ("L", r#"\mathbf{L}"#),
+    ("M", r#"\mathbf{M}"#),
+    ("N", r#"\mathbf{N}"#),
+    ("O", r#"\mathbf{O}"#),
+    ("P", r#"\mathbf{P}"#),
+    ("Q", r#"\mathbf{Q}"#),
+    ("R", r#"\mathbf{R}"#),
+    ("S", r#"\mathbf{S}"#),
+    ("T", r#"\mathbf{T}"#),
+    ("U", r#"\mathbf{U}"#),
+    ("V", r#"\mathbf{V}"#),
+    ("W", r#"\mathbf{W}"#),
+    ("X", r#"\mathbf{X}"#),
+    ("Y", r#"\mathbf{Y}"#),
+    ("Z", r#"\mathbf{Z}"#),
 ];
 pub const LABELLED_COMPONENTS: [&'static str; 10] = [
     r#"\hat{\mathbf{i}}"#,
@@ -455,7 +456,9 @@ pub fn format_i32_fraction(numerator: i32, denominator: i32) -> String {
 }
 
 pub fn format_random_identifier_raw() -> &'static str {
-    LABELLED_IDENTIFIERS.choose(&mut rand::thread_rng()).unwrap()
+    // T is left out so a lone identifier can't be mistaken for time
+    let identifiers: Vec<_> = LABELLED_IDENTIFIERS.iter().filter(|(raw, _)| *raw != "T").collect();
+    helper::with_rng(|rng| identifiers.choose(rng)).unwrap().1
 }
 
 pub fn format_random_identifier() -> String {
@@ -463,7 +466,7 @@ pub fn format_random_identifier() -> String {
 }
 
 pub fn format_random_greek_identifier_raw() -> &'static str {
-    LABELLED_SYMBOLS.choose(&mut rand::thread_rng()).unwrap()
+    helper::with_rng(|rng| LABELLED_SYMBOLS.choose(rng)).unwrap().1
 }
 
 pub fn format_random_greek_identifier() -> String {
@@ -1,8 +1,28 @@
+use ::std::cell::RefCell;
 use rand::{
     Rng,
+    SeedableRng,
+    rngs::StdRng,
     distributions::{Distribution, Uniform},
+    seq::SliceRandom,
 };
 
+use super::locale;
+use super::question::Choice;
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+// Everything random in a template goes through here so the same seed always gives the same paper
+pub fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+pub fn reseed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
 struct Filter<Dist, Test> {
     dist: Dist,
     test: Test,
@@ -23,17 +43,16 @@ where
 }
 
 pub fn gen_range_i32(min: i32, max: i32) -> i32 {
-    rand::thread_rng().gen_range(min..max)
+    with_rng(|rng| rng.gen_range(min..max))
 }
 
 pub fn gen_range_i32_except(min: i32, max: i32, except: i32) -> i32 {
-    let mut rng = rand::thread_rng();
-    loop {
+    with_rng(|rng| loop {
         let num = rng.gen_range(min..max);
         if num != except {
             return num;
         }
-    }
+    })
 }
 
 pub fn gen_range_i32_except_within_range(min: i32, max: i32, except_min: i32, except_max: i32) -> i32 {
@@ -41,21 +60,20 @@ pub fn gen_range_i32_except_within_range(min: i32, max: i32, except_min: i32, ex
         dist: Uniform::new(min, max),
         test: |x: &_| x < &except_min || x > &except_max,
     };
-    rand::thread_rng().sample(&dist)
+    with_rng(|rng| rng.sample(&dist))
 }
 
 pub fn gen_range_f32(min: f32, max: f32) -> f32 {
-    rand::thread_rng().gen_range(min..max)
+    with_rng(|rng| rng.gen_range(min..max))
 }
 
 pub fn gen_range_f32_except(min: f32, max: f32, except: f32) -> f32 {
-    let mut rng = rand::thread_rng();
-    loop {
+    with_rng(|rng| loop {
         let num = rng.gen_range(min..max);
         if num != except {
             return num;
         }
-    }
+    })
 }
 
 pub fn gen_range_f32_except_within_range(min: f32, max: f32, except_min: f32, except_max: f32) -> f32 {
@@ -63,39 +81,29 @@ pub fn gen_range_f32_except_within_range(min: f32, max: f32, except_min: f32, ex
         dist: Uniform::new(min, max),
         test: |x: &_| x < &except_min || x > &except_max,
     };
-    rand::thread_rng().sample(&dist)
+    with_rng(|rng| rng.sample(&dist))
 }
 
-pub fn get_particle_object_name() -> (&'static str, &'static str) {
-    let mut rng = rand::thread_rng();
-    let object_type = [
-        ("A", "ball"),
-        ("A", "rock"),
-        ("A", "car"),
-        ("A", "volleyball"),
-        ("A", "truck"),
-        ("A", "boat"),
-        ("A", "plane"),
-        ("A", "rocket"),
-        ("A", "ship"),
-        ("A", "mass"),
-    ];
-    object_type[rng.gen_range(0..object_type.len())]
+// Read as they would at the start of a sentence in the current locale, e.g. "A ball"
+pub fn get_particle_object_name() -> &'static str {
+    let object_type = locale::list("object.particle");
+    object_type[with_rng(|rng| rng.gen_range(0..object_type.len()))]
 }
 
-pub fn get_long_object_name() -> (&'static str, &'static str) {
-    let mut rng = rand::thread_rng();
-    let object_type = [
-        ("A", "beam"),
-        ("A", "ladder"),
-        ("A", "pole"),
-        ("A", "metal pole"),
-        ("A", "steel beam"),
-        ("A", "stick"),
-    ];
-    object_type[rng.gen_range(0..object_type.len())]
+pub fn get_long_object_name() -> &'static str {
+    let object_type = locale::list("object.long");
+    object_type[with_rng(|rng| rng.gen_range(0..object_type.len()))]
 }
 
 pub fn coin_flip() -> bool {
-    rand::thread_rng().gen_bool(0.5)
+    with_rng(|rng| rng.gen_bool(0.5))
+}
+
+// Mixes the correct answer in with the distractors. Templates pick their values so every distractor
+// reads differently, dropping one here would quietly lose the misconception it tests for
+pub fn shuffle_choices(correct: Choice, distractors: Vec<Choice>) -> Vec<Choice> {
+    let mut choices = vec![correct];
+    choices.extend(distractors);
+    with_rng(|rng| choices.shuffle(rng));
+    choices
 }
@@ -0,0 +1,96 @@
+/*
+ * Message catalogs for everything a student reads, one file per locale in ./locale.
+ *
+ * Each line is `key = text`, lines starting with # are comments. Placeholders are written
+ * {$name} so they don't get mixed up with LaTeX braces. Text that differs between the raw and
+ * LaTeX forms uses two keys ending in .raw and .latex, anything else is shared by both. Keys a
+ * catalog doesn't have fall back to en-GB, so a partial translation still generates.
+ */
+
+use ::std::cell::Cell;
+use ::std::collections::HashMap;
+use ::std::fmt::Display;
+use ::std::sync::OnceLock;
+use common_types::Generate::Locale;
+
+const EN_GB: &str = include_str!("locale/en-GB.txt");
+const CY: &str = include_str!("locale/cy.txt");
+
+thread_local! {
+    static LOCALE: Cell<Locale> = const { Cell::new(Locale::EnGb) };
+}
+
+// Templates are written without a locale, everything generated on this thread from now on uses this one
+pub fn set(locale: Locale) {
+    LOCALE.with(|current| current.set(locale));
+}
+
+pub fn current() -> Locale {
+    LOCALE.with(Cell::get)
+}
+
+pub fn catalog(locale: Locale) -> &'static HashMap<&'static str, &'static str> {
+    static EN_GB_CATALOG: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
+    static CY_CATALOG: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
+    match locale {
+        Locale::EnGb => EN_GB_CATALOG.get_or_init(|| parse(EN_GB)),
+        Locale::Cy => CY_CATALOG.get_or_init(|| parse(CY)),
+    }
+}
+
+fn parse(source: &'static str) -> HashMap<&'static str, &'static str> {
+    source.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, text)| (key.trim(), text.trim()))
+        .collect()
+}
+
+// Falls back to en-GB and then to the key itself so a missing message shows up in the paper
+// rather than failing the whole generation
+pub fn message_in(locale: Locale, key: &str) -> &str {
+    catalog(locale).get(key)
+        .or_else(|| catalog(Locale::EnGb).get(key))
+        .copied()
+        .unwrap_or(key)
+}
+
+pub fn message(key: &str) -> &str {
+    message_in(current(), key)
+}
+
+// Whitespace separated list, or | separated when the items have spaces in them
+pub fn list(key: &str) -> Vec<&str> {
+    let text = message(key);
+    if text.contains('|') {
+        text.split('|').map(str::trim).collect()
+    } else {
+        text.split_whitespace().collect()
+    }
+}
+
+pub fn format(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    substitute(message(key), args)
+}
+
+// Raw and LaTeX forms of the same message, each argument is given in both forms too
+pub fn pair(key: &str, args: &[(&str, &dyn Display, &dyn Display)]) -> (String, String) {
+    let raw_args = args.iter().map(|(name, raw, _)| (*name, *raw)).collect::<Vec<(&str, &dyn Display)>>();
+    let latex_args = args.iter().map(|(name, _, latex)| (*name, *latex)).collect::<Vec<(&str, &dyn Display)>>();
+    let raw_key = format!("{key}.raw");
+    let latex_key = format!("{key}.latex");
+    if message(&raw_key) == raw_key {
+        let text = message(key);
+        return (substitute(text, &raw_args), substitute(text, &latex_args));
+    }
+    (substitute(message(&raw_key), &raw_args), substitute(message(&latex_key), &latex_args))
+}
+
+fn substitute(text: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut result = text.to_string();
+    for (name, value) in args.iter() {
+        result = result.replace(&format!("{{${name}}}"), &value.to_string());
+    }
+    result
+}
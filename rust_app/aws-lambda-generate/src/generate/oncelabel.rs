@@ -1,26 +1,50 @@
 use rand::Rng;
 
-use super::formatter;
+use super::{engine::registry::Template, formatter, helper, locale};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label {
+    pub raw: &'static str,
+    pub latex: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelError {
+    LabelsExhausted,
+    SymbolsExhausted,
+}
 
 pub struct OnceLabel {
-    free_labels: Vec<&'static str>,
-    free_raw_labels: Vec<&'static str>,
-    free_symbols: Vec<&'static str>,
+    free_labels: Vec<Label>,
+    free_symbols: Vec<Label>,
 }
 impl OnceLabel {
-    pub fn new() -> Self {
+    pub fn new(reserved: &[&str]) -> Self {
+        // Letters that aren't in the locale's alphabet would look out of place as labels
+        let unavailable = locale::list("labels.unavailable");
+        let free = |pool: &[(&'static str, &'static str)]| pool.iter()
+            .filter(|(raw, _)| !reserved.contains(raw) && !unavailable.contains(raw))
+            .map(|&(raw, latex)| Label { raw, latex })
+            .collect();
         Self {
-            free_labels: Vec::from(formatter::LABELLED_IDENTIFIERS),
-            free_raw_labels: Vec::from(formatter::LABELLED_IDENTIFIERS_RAW),
-            free_symbols: Vec::from(formatter::LABELLED_SYMBOLS),
+            free_labels: free(&formatter::LABELLED_IDENTIFIERS),
+            free_symbols: free(&formatter::LABELLED_SYMBOLS),
         }
     }
-    pub fn next_symbol_raw(&mut self) -> &'static str {
-        let index = rand::thread_rng().gen_range(0..self.free_symbols.len());
-        self.free_symbols.swap_remove(index)
+    pub fn for_template(template: &Template) -> Self {
+        Self::new(template.reserved_labels)
     }
-    pub fn next_label_raw(&mut self) -> (&'static str, &'static str) {
-        let index = rand::thread_rng().gen_range(0..self.free_labels.len());
-        (self.free_labels.swap_remove(index), self.free_raw_labels.swap_remove(index))
+    pub fn next_symbol(&mut self) -> Result<Label, LabelError> {
+        Self::take(&mut self.free_symbols).ok_or(LabelError::SymbolsExhausted)
+    }
+    pub fn next_label(&mut self) -> Result<Label, LabelError> {
+        Self::take(&mut self.free_labels).ok_or(LabelError::LabelsExhausted)
+    }
+    fn take(free: &mut Vec<Label>) -> Option<Label> {
+        if free.is_empty() {
+            return None;
+        }
+        let index = helper::with_rng(|rng| rng.gen_range(0..free.len()));
+        Some(free.swap_remove(index))
     }
 }
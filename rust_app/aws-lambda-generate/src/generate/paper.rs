@@ -1,42 +1,113 @@
+use ::std::collections::HashSet;
 use chrono::{NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
-use rmp_serde::{Deserializer, Serializer};
-use common_types::Generate::{GenerateId, GenerateOption};
+use common_types::Generate::{GenerateId, GenerateOption, Locale, PaperComposition, PERSONALISED_CATEGORY};
 
-use super::engine::{self, math, GenerateResult};
+use super::engine::{self, GenerateResult, personalise::{MarkedAnswer, Planner}};
+use super::locale;
 use super::question::QuestionType;
 
+pub mod blob;
+
+const MINUTES_PER_MARK_TENTHS: u32 = 12;
+
 #[derive(Deserialize, Serialize)]
 pub struct Paper {
     questions: Vec<QuestionType>,
     created_by: i64,
     created_on: NaiveDateTime,
-    generated_catagory: GenerateId,
+    // None for personalised papers
+    generated_catagory: Option<GenerateId>,
     generated_options: Vec<GenerateOption>,
+    composition: PaperComposition,
+    #[serde(default)]
+    total_marks: u32,
+    #[serde(default)]
+    suggested_minutes: u32,
+    // One per template run, see engine::fingerprint
+    #[serde(default)]
+    fingerprints: Vec<String>,
+    #[serde(default)]
+    locale: Locale,
 }
 impl Paper {
-    pub fn new(created_by: i64, generated_catagory: GenerateId, generated_options: Vec<GenerateOption>) -> Self {
+    pub fn new(created_by: i64, generated_catagory: Option<GenerateId>, generated_options: Vec<GenerateOption>, composition: Option<PaperComposition>) -> Self {
+        let composition = composition.unwrap_or_else(|| PaperComposition::per_topic(generated_options.len()));
         Self {
             created_by,
             generated_catagory,
             generated_options,
+            composition,
             created_on: Utc::now().naive_utc(),
             questions: Vec::new(),
+            total_marks: 0,
+            suggested_minutes: 0,
+            fingerprints: Vec::new(),
+            locale: Locale::default(),
         }
     }
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
     pub fn populate(&mut self) -> GenerateResult<()> {
+        self.populate_avoiding(&HashSet::new())
+    }
+    // Questions whose fingerprint is in `recent` are re-rolled, used to stop a user seeing the same
+    // question in back to back papers
+    pub fn populate_avoiding(&mut self, recent: &HashSet<String>) -> GenerateResult<()> {
+        // Options are checked against the category when the request is made, templates are
+        // registered by topic so the category doesn't matter past this point
+        locale::set(self.locale);
+        (self.questions, self.fingerprints) = engine::generate_from_composition(&self.generated_options, &self.composition, recent)?;
+        self.total_marks = self.questions.iter().map(QuestionType::marks).sum();
+        self.suggested_minutes = suggested_minutes(self.total_marks);
+        Ok(())
+    }
+    // Topics are picked from the user's marked answers, generated_options ends up as whatever was picked
+    pub fn populate_personalised(&mut self, history: &[MarkedAnswer], recent: &HashSet<String>) -> GenerateResult<()> {
+        locale::set(self.locale);
+        let planner = Planner::new(history, self.created_on);
+        (self.questions, self.fingerprints, self.generated_options) = engine::generate_personalised(&planner, self.composition.length, recent)?;
+        self.total_marks = self.questions.iter().map(QuestionType::marks).sum();
+        self.suggested_minutes = suggested_minutes(self.total_marks);
+        Ok(())
+    }
+    pub fn questions(&self) -> &[QuestionType] {
+        &self.questions
+    }
+    pub fn created_on(&self) -> NaiveDateTime {
+        self.created_on
+    }
+    pub fn generated_catagory(&self) -> Option<&GenerateId> {
+        self.generated_catagory.as_ref()
+    }
+    // What the paper is titled with
+    pub fn category_name(&self) -> String {
         match self.generated_catagory {
-            GenerateId::MathsCore => {
-                self.questions = math::pure::generate_from_options(engine::GENERATE_QUESTIONS_PER_TOPIC, &self.generated_options)?;
-            },
-            GenerateId::MathsMechanics => {
-                self.questions = math::mechanics::generate_from_options(engine::GENERATE_QUESTIONS_PER_TOPIC, &self.generated_options)?;
-            },
-            GenerateId::MathsStatistics => {
-                self.questions = math::statistics::generate_from_options(engine::GENERATE_QUESTIONS_PER_TOPIC, &self.generated_options)?;
-            },
-            _ => ()
+            Some(ref generated_catagory) => generated_catagory.to_string(),
+            None => PERSONALISED_CATEGORY.to_string(),
         }
-        Ok(())
     }
+    pub fn generated_options(&self) -> &[GenerateOption] {
+        &self.generated_options
+    }
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+    pub fn fingerprints(&self) -> &[String] {
+        &self.fingerprints
+    }
+    pub fn total_marks(&self) -> u32 {
+        self.total_marks
+    }
+    pub fn suggested_minutes(&self) -> u32 {
+        self.suggested_minutes
+    }
+}
+
+// A-level papers give 2 hours for 100 marks, round up to the next 5 minutes like a real paper would
+fn suggested_minutes(total_marks: u32) -> u32 {
+    let minutes = (total_marks * MINUTES_PER_MARK_TENTHS).div_ceil(10);
+    minutes.div_ceil(5) * 5
 }
@@ -0,0 +1,186 @@
+/*
+ * Everything inside the gzip stream of a `.rapidl.gz` looks like
+ *
+ *   | "RPDL" | schema version (u16, big endian) | generator version length (u8) | generator version | msgpack Paper |
+ *
+ * Papers written before the header existed are bare msgpack and are read as schema version 0.
+ * Bump SCHEMA_VERSION whenever Paper or anything inside it changes shape, keep the old shape
+ * below as its own module and add a migration from it to the next version. Fields appended to the
+ * end with #[serde(default)] don't need a module, the current reader handles the older version too.
+ */
+
+use ::std::io::{Read, Write};
+use serde::Serialize;
+use rmp_serde::Serializer;
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+
+use super::Paper;
+
+pub const MAGIC: &[u8; 4] = b"RPDL";
+pub const SCHEMA_VERSION: u16 = 6;
+pub const GENERATOR_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug)]
+pub enum BlobError {
+    SerializeError,
+    DeserializeError,
+    CompressionError,
+    TruncatedHeader,
+    UnknownSchemaVersion(u16),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobHeader {
+    pub schema_version: u16,
+    // None for papers written before the header existed
+    pub generator_version: Option<String>,
+}
+
+pub fn encode(paper: &Paper) -> Result<Vec<u8>, BlobError> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&SCHEMA_VERSION.to_be_bytes());
+    buf.push(GENERATOR_VERSION.len() as u8);
+    buf.extend_from_slice(GENERATOR_VERSION.as_bytes());
+    paper.serialize(&mut Serializer::new(&mut buf)).map_err(|_| BlobError::SerializeError)?;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&buf).map_err(|_| BlobError::CompressionError)?;
+    encoder.finish().map_err(|_| BlobError::CompressionError)
+}
+
+// Reads a paper of any known schema version and migrates it forward to the current one
+pub fn decode(gzipped: &[u8]) -> Result<(BlobHeader, Paper), BlobError> {
+    let mut buf = Vec::new();
+    GzDecoder::new(gzipped).read_to_end(&mut buf).map_err(|_| BlobError::CompressionError)?;
+    decode_uncompressed(&buf)
+}
+
+pub fn decode_uncompressed(buf: &[u8]) -> Result<(BlobHeader, Paper), BlobError> {
+    let (header, body) = read_header(buf)?;
+    let paper = match header.schema_version {
+        0 => current::migrate_v0(v0::read(body)?),
+        // v1 only lacks the trailing fingerprints field, v2 the question metadata, v3 header
+        // diagrams and v4 the locale, all of which default. Up to v5 the category was always set,
+        // which msgpack reads back as Some
+        1..=6 => current::read(body)?,
+        version => return Err(BlobError::UnknownSchemaVersion(version)),
+    };
+    Ok((header, paper))
+}
+
+fn read_header(buf: &[u8]) -> Result<(BlobHeader, &[u8]), BlobError> {
+    let Some(rest) = buf.strip_prefix(MAGIC.as_slice()) else {
+        return Ok((BlobHeader { schema_version: 0, generator_version: None }, buf));
+    };
+    if rest.len() < 3 {
+        return Err(BlobError::TruncatedHeader);
+    }
+    let (fixed, rest) = rest.split_at(3);
+    let schema_version = u16::from_be_bytes([fixed[0], fixed[1]]);
+    let generator_len = fixed[2] as usize;
+    if rest.len() < generator_len {
+        return Err(BlobError::TruncatedHeader);
+    }
+    let (generator_version, body) = rest.split_at(generator_len);
+    let header = BlobHeader {
+        schema_version,
+        generator_version: Some(String::from_utf8_lossy(generator_version).into_owned()),
+    };
+    Ok((header, body))
+}
+
+// The first shape of Paper, before composition, marks and worked solution steps were added
+mod v0 {
+    use chrono::NaiveDateTime;
+    use serde::Deserialize;
+    use common_types::Generate::{GenerateId, GenerateOption};
+
+    use super::BlobError;
+
+    #[derive(Deserialize)]
+    pub enum QuestionType {
+        Single(Question),
+        Grouped(QuestionHeader, Vec<QuestionType>),
+    }
+
+    #[derive(Deserialize)]
+    pub struct QuestionHeader {
+        pub raw_text: String,
+        pub latex_text: String,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Question {
+        pub header: QuestionHeader,
+        pub raw_text: String,
+        pub latex_text: String,
+        pub mark_scheme: MarkScheme,
+    }
+
+    #[derive(Deserialize)]
+    pub struct MarkScheme {
+        pub raw_text: String,
+        pub latex_text: String,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Paper {
+        pub questions: Vec<QuestionType>,
+        pub created_by: i64,
+        pub created_on: NaiveDateTime,
+        pub generated_catagory: GenerateId,
+        pub generated_options: Vec<GenerateOption>,
+    }
+
+    pub fn read(body: &[u8]) -> Result<Paper, BlobError> {
+        rmp_serde::from_slice(body).map_err(|_| BlobError::DeserializeError)
+    }
+}
+
+mod current {
+    use common_types::Generate::PaperComposition;
+
+    use crate::generate::question::{MarkScheme, Question, QuestionHeader, QuestionType};
+    use super::{v0, BlobError, Paper};
+
+    pub fn read(body: &[u8]) -> Result<Paper, BlobError> {
+        rmp_serde::from_slice(body).map_err(|_| BlobError::DeserializeError)
+    }
+
+    // Old papers were always three questions per topic and had no marks to carry over
+    pub fn migrate_v0(paper: v0::Paper) -> Paper {
+        let composition = PaperComposition::per_topic(paper.generated_options.len());
+        Paper {
+            questions: paper.questions.into_iter().map(migrate_question_type).collect(),
+            created_by: paper.created_by,
+            created_on: paper.created_on,
+            generated_catagory: Some(paper.generated_catagory),
+            generated_options: paper.generated_options,
+            composition,
+            total_marks: 0,
+            suggested_minutes: 0,
+            fingerprints: Vec::new(),
+            locale: Default::default(),
+        }
+    }
+
+    fn migrate_question_type(question_type: v0::QuestionType) -> QuestionType {
+        match question_type {
+            v0::QuestionType::Single(question) => QuestionType::Single(Question::from(
+                migrate_header(question.header),
+                question.raw_text,
+                question.latex_text,
+                MarkScheme::from(question.mark_scheme.raw_text, question.mark_scheme.latex_text),
+            )),
+            v0::QuestionType::Grouped(header, questions) => QuestionType::Grouped(
+                migrate_header(header),
+                questions.into_iter().map(migrate_question_type).collect(),
+            ),
+        }
+    }
+
+    fn migrate_header(header: v0::QuestionHeader) -> QuestionHeader {
+        QuestionHeader::new(header.raw_text, header.latex_text)
+    }
+}
@@ -1,15 +1,23 @@
+use ::std::fmt;
 use serde::{Deserialize, Serialize};
+use common_types::Generate::{GenerateOption, Locale};
+
+use super::diagram::Diagram;
+use super::locale;
 
 #[derive(Deserialize, Serialize, Debug)]
 pub enum QuestionType {
     Single(Question),
     Grouped(QuestionHeader, Vec<QuestionType>),
+    MultipleChoice(Question, Vec<Choice>),
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct QuestionHeader {
     pub raw_text: String,
     pub latex_text: String,
+    #[serde(default)]
+    pub diagram: Option<Diagram>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -18,14 +26,81 @@ pub struct Question {
     pub raw_text: String,
     pub latex_text: String,
     pub mark_scheme: MarkScheme,
+    // Always the total of the mark scheme, kept alongside so readers of the blob don't need to add it up
+    #[serde(default)]
+    pub marks: u32,
+    // Filled in by the engine once the template has run, None for questions from old papers
+    #[serde(default)]
+    pub metadata: Option<QuestionMetadata>,
+}
+
+// Where a question came from, enough to run the template again and get the same question back
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct QuestionMetadata {
+    pub topic: GenerateOption,
+    pub template_id: String,
+    pub seed: u64,
+    pub difficulty: u8,
+    // Exam board specification code followed by the section of it, e.g. "9MA0 7.4"
+    pub spec_code: String,
+    pub tags: Vec<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct MarkScheme {
+    // Whole worked solution as one paragraph, built from the steps when there are any
+    pub raw_text: String,
+    pub latex_text: String,
+    #[serde(default)]
+    pub steps: Vec<MarkStep>,
+}
+
+// One line of a worked solution, clients can reveal these one at a time as hints
+#[derive(Deserialize, Serialize, Debug)]
+pub struct MarkStep {
+    pub explanation: StepText,
+    pub working: StepText,
+    pub result: StepText,
+    pub mark: Option<Mark>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Choice {
+    pub raw_text: String,
+    pub latex_text: String,
+    // None for the correct answer, distractors say which mistake leads to them
+    pub misconception: Option<Misconception>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Misconception {
+    SignError,
+    MissingConstant,
+    WrongTime,
+    DegreeRadian,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct StepText {
     pub raw_text: String,
     pub latex_text: String,
 }
 
+// Exam board style marks, M for method, A for accuracy (depends on the method marks before it)
+// and B for independent marks
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkKind {
+    M,
+    A,
+    B,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mark {
+    pub kind: MarkKind,
+    pub value: u32,
+}
+
 impl QuestionType {
     pub fn into_grouped(self) -> Self {
         match self {
@@ -35,9 +110,37 @@ impl QuestionType {
                 }
                 Self::Grouped(question.header, vec![ QuestionType::Single( Question::from(QuestionHeader::new("", ""), question.raw_text, question.latex_text, question.mark_scheme)) ])
             },
+            QuestionType::MultipleChoice(question, choices) => {
+                Self::Grouped(question.header, vec![ QuestionType::MultipleChoice( Question::from(QuestionHeader::new("", ""), question.raw_text, question.latex_text, question.mark_scheme), choices) ])
+            },
             _ => panic!("can only transform single to grouped")
         }
     }
+    pub fn for_each_question_mut(&mut self, f: &mut impl FnMut(&mut Question)) {
+        match self {
+            QuestionType::Single(question) | QuestionType::MultipleChoice(question, _) => f(question),
+            QuestionType::Grouped(_, questions) => questions.iter_mut().for_each(|question| question.for_each_question_mut(f)),
+        }
+    }
+    // Every header and body in plain text, what the question actually says regardless of how it was made
+    pub fn push_raw_text(&self, text: &mut String) {
+        match self {
+            QuestionType::Single(question) | QuestionType::MultipleChoice(question, _) => {
+                text.push_str(&question.header.raw_text);
+                text.push_str(&question.raw_text);
+            },
+            QuestionType::Grouped(header, questions) => {
+                text.push_str(&header.raw_text);
+                questions.iter().for_each(|question| question.push_raw_text(text));
+            },
+        }
+    }
+    pub fn marks(&self) -> u32 {
+        match self {
+            QuestionType::Single(question) | QuestionType::MultipleChoice(question, _) => question.marks,
+            QuestionType::Grouped(_, questions) => questions.iter().map(QuestionType::marks).sum(),
+        }
+    }
 }
 
 impl QuestionHeader {
@@ -45,7 +148,11 @@ impl QuestionHeader {
     where
         T: ToString
     {
-        Self { raw_text: raw_text.to_string(), latex_text: latex_text.to_string() }
+        Self { raw_text: raw_text.to_string(), latex_text: latex_text.to_string(), diagram: None }
+    }
+    pub fn with_diagram(mut self, diagram: Diagram) -> Self {
+        self.diagram = Some(diagram);
+        self
     }
 }
 
@@ -56,17 +163,22 @@ impl Question {
             raw_text: String::new(),
             latex_text: String::new(),
             mark_scheme: MarkScheme::new(),
+            marks: 0,
+            metadata: None,
         }
     }
     pub fn from(header: QuestionHeader, raw_text: String, latex_text: String, mark_scheme: MarkScheme) -> Self {
-        Self { header, raw_text, latex_text, mark_scheme }
+        let marks = mark_scheme.total();
+        Self { header, raw_text, latex_text, mark_scheme, marks, metadata: None }
     }
     pub fn from_header_and_scheme(header: QuestionHeader, mark_scheme: MarkScheme) -> Self {
         Self {
             header,
+            marks: mark_scheme.total(),
             mark_scheme,
             raw_text: String::new(),
             latex_text: String::new(),
+            metadata: None,
         }
     }
     pub fn is_empty(&self) -> bool {
@@ -79,15 +191,123 @@ impl MarkScheme {
         Self {
             raw_text: String::new(),
             latex_text: String::new(),
+            steps: Vec::new(),
         }
     }
     pub fn from(raw_text: String, latex_text: String) -> Self {
         Self {
             raw_text,
             latex_text,
+            steps: Vec::new(),
+        }
+    }
+    pub fn from_steps(steps: Vec<MarkStep>) -> Self {
+        let raw_text = join_step_text(&steps, |text| &text.raw_text);
+        let latex_text = join_step_text(&steps, |text| &text.latex_text);
+        Self { raw_text, latex_text, steps }
+    }
+    pub fn marks(&self) -> impl Iterator<Item = &Mark> {
+        self.steps.iter().filter_map(|step| step.mark.as_ref())
+    }
+    pub fn total(&self) -> u32 {
+        self.marks().map(|mark| mark.value).sum()
+    }
+    // Annotation as it would appear in the margin of a mark scheme, e.g. "M1 M1 A1"
+    pub fn annotation(&self) -> String {
+        self.marks().map(Mark::to_string).collect::<Vec<String>>().join(" ")
+    }
+    pub fn is_empty(&self) -> bool {
+        self.raw_text.is_empty() && self.latex_text.is_empty() && self.steps.is_empty()
+    }
+}
+
+impl MarkStep {
+    pub fn new<T>(raw_explanation: T, latex_explanation: T) -> Self
+    where
+        T: ToString
+    {
+        Self {
+            explanation: StepText::new(raw_explanation, latex_explanation),
+            working: StepText::default(),
+            result: StepText::default(),
+            mark: None,
         }
     }
+    pub fn working<T>(mut self, raw_text: T, latex_text: T) -> Self
+    where
+        T: ToString
+    {
+        self.working = StepText::new(raw_text, latex_text);
+        self
+    }
+    pub fn result<T>(mut self, raw_text: T, latex_text: T) -> Self
+    where
+        T: ToString
+    {
+        self.result = StepText::new(raw_text, latex_text);
+        self
+    }
+    pub fn mark(mut self, mark: Mark) -> Self {
+        self.mark = Some(mark);
+        self
+    }
+}
+
+impl Choice {
+    pub fn correct(raw_text: String, latex_text: String) -> Self {
+        Self { raw_text, latex_text, misconception: None }
+    }
+    pub fn distractor(raw_text: String, latex_text: String, misconception: Misconception) -> Self {
+        Self { raw_text, latex_text, misconception: Some(misconception) }
+    }
+    pub fn is_correct(&self) -> bool {
+        self.misconception.is_none()
+    }
+}
+
+impl Misconception {
+    // Shown to the student when they pick a distractor
+    pub fn feedback(&self, locale: Locale) -> &'static str {
+        let key = match self {
+            Misconception::SignError => "misconception.sign_error",
+            Misconception::MissingConstant => "misconception.missing_constant",
+            Misconception::WrongTime => "misconception.wrong_time",
+            Misconception::DegreeRadian => "misconception.degree_radian",
+        };
+        locale::message_in(locale, key)
+    }
+}
+
+impl StepText {
+    pub fn new<T>(raw_text: T, latex_text: T) -> Self
+    where
+        T: ToString
+    {
+        Self { raw_text: raw_text.to_string(), latex_text: latex_text.to_string() }
+    }
     pub fn is_empty(&self) -> bool {
         self.raw_text.is_empty() && self.latex_text.is_empty()
     }
 }
+
+fn join_step_text(steps: &[MarkStep], text: fn(&StepText) -> &String) -> String {
+    steps.iter()
+        .flat_map(|step| [&step.explanation, &step.working, &step.result])
+        .map(text)
+        .filter(|text| !text.is_empty())
+        .map(String::as_str)
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+impl Mark {
+    pub const M1: Self = Self { kind: MarkKind::M, value: 1 };
+    pub const A1: Self = Self { kind: MarkKind::A, value: 1 };
+    pub const B1: Self = Self { kind: MarkKind::B, value: 1 };
+}
+
+impl fmt::Display for Mark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}{}", self.kind, self.value)
+    }
+}
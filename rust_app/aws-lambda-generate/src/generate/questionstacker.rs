@@ -1,35 +1,74 @@
-use super::question::{Question, QuestionType};
+use super::question::{Choice, Question, QuestionMetadata, QuestionType};
 
 #[derive(Debug)]
 pub struct Stacker {
     questions: Vec<QuestionType>,
+    parameters: Vec<i32>,
+    metadata: Option<QuestionMetadata>,
+    tags: Vec<String>,
 }
 impl Stacker {
     pub fn new() -> Self {
         Self {
             questions: Vec::new(),
+            parameters: Vec::new(),
+            metadata: None,
+            tags: Vec::new(),
         }
     }
+    // Tags for the variant the template rolled, added on top of the template's own tags
+    pub fn tag<T: ToString>(&mut self, tag: T) {
+        self.tags.push(tag.to_string());
+    }
+    // Set by the engine after the template has run, every question gets a copy when consumed
+    pub fn set_metadata(&mut self, metadata: QuestionMetadata) {
+        self.metadata = Some(metadata);
+    }
+    // The random values that make this question different from others from the same template,
+    // two runs with the same parameters are treated as the same question
+    pub fn fingerprint_parameters(&mut self, parameters: &[i32]) {
+        self.parameters.extend_from_slice(parameters);
+    }
+    pub fn parameters(&self) -> &[i32] {
+        &self.parameters
+    }
+    pub fn raw_text(&self) -> String {
+        let mut text = String::new();
+        self.questions.iter().for_each(|question| question.push_raw_text(&mut text));
+        text
+    }
     pub fn next_root_question(&mut self, question: Question) {
         let question = QuestionType::Single(question);
         self.questions.push(question);
     }
     pub fn next_root_sub_question(&mut self, question: Question) {
+        self.next_root_sub_question_type( QuestionType::Single(question) )
+    }
+    pub fn next_root_sub_multiple_choice(&mut self, question: Question, choices: Vec<Choice>) {
+        self.next_root_sub_question_type( QuestionType::MultipleChoice(question, choices) )
+    }
+    fn next_root_sub_question_type(&mut self, question: QuestionType) {
         let questions_len = self.questions.len();
         let root_question = &mut self.questions[questions_len - 1];
         let QuestionType::Grouped(_, ref mut questions) = root_question else {
             let root_question = self.questions.pop().unwrap();
             let grouped_root_question = root_question.into_grouped();
             self.questions.push(grouped_root_question);
-            return self.next_root_sub_question( question )
+            return self.next_root_sub_question_type( question )
         };
-        questions.push( QuestionType::Single(question) )
+        questions.push( question )
     }
     pub fn next_depth_sub_question(&mut self, question: Question) {
         let sub_questions = recursive_last_morph(&mut self.questions);
         sub_questions.push( QuestionType::Single(question) )
     }
-    pub fn consume_get_questions(self) -> Vec<QuestionType> {
+    pub fn consume_get_questions(mut self) -> Vec<QuestionType> {
+        if let Some(mut metadata) = self.metadata.take() {
+            metadata.tags.append(&mut self.tags);
+            for question in self.questions.iter_mut() {
+                question.for_each_question_mut(&mut |question| question.metadata = Some(metadata.clone()));
+            }
+        }
         self.questions
     }
 }
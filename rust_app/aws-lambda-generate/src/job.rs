@@ -0,0 +1,339 @@
+use ::std::collections::HashSet;
+use ::std::sync::Arc;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::RunQueryDsl;
+use deadpool_redis::redis::cmd;
+use chrono::{NaiveDateTime, TimeDelta};
+use common_types::{
+    Generate::{GenerateOption, SQSBody},
+    SESContacts::{Command, Request, SendIndividual},
+};
+use common_types_accounts::{
+    Constants,
+    Email,
+    Progress::{self, ProgressEvent},
+    Schema::{generation, markedanswers, users, hooked_sql_types::GenerationStatus},
+    Storage::PaperStore,
+};
+use aws_lambda_generate::generate::{engine::{self, personalise::MarkedAnswer}, paper::{self, blob::{self, BlobError}}};
+
+pub enum GenerationError {
+    PostgresConnectionFailure,
+    PostgresCommandFailure,
+    InternalGenerationFailure(engine::GenerateFailure),
+    SerializeError,
+    UUIDParseFailure,
+    PaperStorePutError,
+    CompressionError,
+    // Every attempt the job was allowed has already been claimed, the count is how many
+    TooManyAttempts(i32),
+    Panicked,
+    DeleteImmediately,
+}
+
+impl GenerationError {
+    // Whether the job could succeed if it were tried again later
+    pub fn is_transient(&self) -> bool {
+        use GenerationError::*;
+        matches!(self, PostgresConnectionFailure | PostgresCommandFailure)
+    }
+    // Recorded against the generation and its dead letter for admins to look at
+    pub fn reason(&self) -> String {
+        use GenerationError::*;
+        match self {
+            PostgresConnectionFailure => "Could not connect to Postgres".to_string(),
+            PostgresCommandFailure => "Postgres command failed".to_string(),
+            InternalGenerationFailure(failure) => format!("Generation failed, {failure:?}"),
+            SerializeError => "Failed to serialize paper".to_string(),
+            UUIDParseFailure => "Job id is not a UUID".to_string(),
+            PaperStorePutError => "Failed to store paper".to_string(),
+            CompressionError => "Failed to compress paper".to_string(),
+            TooManyAttempts(attempts) => format!("Gave up after {attempts} attempts"),
+            Panicked => "Generator panicked".to_string(),
+            DeleteImmediately => "Job no longer needs generating".to_string(),
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = generation)]
+#[allow(non_snake_case)]
+struct InsertableGeneration {
+    userid: i64,
+    status: GenerationStatus,
+    createdat: NaiveDateTime,
+    finishedon: NaiveDateTime,
+    jobid: uuid::Uuid,
+}
+
+// Lets anyone polling the job's status know how far it has got, a paper is generated in one go so this
+// moves in steps rather than per question
+async fn report_progress(appstate: &common_types_accounts::MinimalState::AppState, job_id: uuid::Uuid, status: GenerationStatus, progress: u8) {
+    let Ok(mut redis_conn) = appstate.redis.get().await else {
+        tracing::warn!("Failed to get redis connection, progress for {job_id} not published");
+        return;
+    };
+    if let Err(err) = Progress::publish(&mut redis_conn, job_id, &ProgressEvent::new(status, progress)).await {
+        tracing::warn!("Failed to publish progress for {job_id}, {:?}", err);
+    }
+}
+
+// For users who asked to be told when their paper is ready, failing to send only gets logged as the paper is already done
+async fn notify_ready(appstate: &common_types_accounts::MinimalState::AppState, lambda_client: &aws_sdk_lambda::Client, user_id: i64, job_id: uuid::Uuid) {
+    let user = {
+        let Ok(mut postgres_conn) = appstate.postgres.get().await else {
+            tracing::error!("Failed to open postgres connection, {user_id} won't be told {job_id} is ready");
+            return;
+        };
+        users::table.find(user_id)
+            .select((users::email, users::emailverified))
+            .first::<(String, bool)>(&mut postgres_conn)
+            .await
+    };
+    let email = match user {
+        Ok((email, true)) => email,
+        Ok((_, false)) => {
+            tracing::info!("Email for {user_id} is unverified, not telling them {job_id} is ready");
+            return;
+        },
+        Err(err) => {
+            tracing::error!("Failed to query email for {user_id}, {err}");
+            return;
+        },
+    };
+    if !Email::is_safe_to_send_to(&appstate.postgres, &email).await {
+        tracing::warn!("Email for {user_id} is not safe to send to, not telling them {job_id} is ready");
+        return;
+    }
+
+    let template = SendIndividual {
+        template_name: "paperreadytemplate".to_string(),
+        template_data: format!(r#"{{ "paperurl": "{}/generated?id={job_id}" }}"#, *Constants::ORIGIN_URL),
+    };
+    let lambda_request = Request {
+        commands: Command::SendIndividual(template),
+        email,
+    };
+    let lambda_response = lambda_client
+                            .invoke()
+                            .function_name(&*Constants::LAMBDA_EMAIL_ARN)
+                            .invocation_type(aws_sdk_lambda::types::InvocationType::Event)
+                            .payload(aws_sdk_lambda::primitives::Blob::new(serde_json::to_string(&lambda_request).unwrap()))
+                            .send()
+                            .await;
+    if let Err(err) = lambda_response {
+        tracing::error!("Failed to invoke email lambda for {job_id}, {err}");
+    }
+}
+
+pub async fn generate(appstate: common_types_accounts::MinimalState::AppState, paper_store: Arc<dyn PaperStore>, lambda_client: aws_sdk_lambda::Client, generate_options: SQSBody) -> Result<(), GenerationError> {
+    let uuid_job_id = uuid::Uuid::try_parse(&generate_options.job_id);
+    let Ok(uuid_job_id) = uuid_job_id else {
+        return Err(GenerationError::UUIDParseFailure);
+    };
+
+    {
+        let mut postgres_conn = appstate.postgres.get()
+                                .await.map_err(|err| {
+                                    tracing::error!("Failed to open postgres connection, {err}");
+                                    GenerationError::PostgresConnectionFailure
+                                })?;
+        let ret = postgres_conn.build_transaction()
+                        .read_write()
+                        .serializable()
+                        .run::<Result<(), GenerationError>, diesel::result::Error, _>(|conn| async move {
+                            let (status, attempts, last_changed): (GenerationStatus, i32, NaiveDateTime) = generation::table.filter(generation::userid.eq(generate_options.user_id).and(generation::jobid.eq(uuid_job_id)))
+                                                .select((generation::status, generation::attempts, generation::lastchanged))
+                                                .for_update()
+                                                .first(conn)
+                                                .await?;
+                            match status {
+                                GenerationStatus::Success => { 
+                                    tracing::error!("Generation {uuid_job_id} is in success state already?"); 
+                                    return Ok(Err(GenerationError::DeleteImmediately));
+                                },
+                                GenerationStatus::Failed => { 
+                                    tracing::warn!("Generation {uuid_job_id} is in failed state but attempted to generate?");
+                                    return Ok(Err(GenerationError::DeleteImmediately));
+                                },
+                                GenerationStatus::Deleting => {
+                                    return Ok(Err(GenerationError::DeleteImmediately));
+                                },
+                                // The message came round again while another worker is still on it, such as after the queue's
+                                // visibility timeout ran out. If that worker has died the reaper queues the job again
+                                GenerationStatus::Working if last_changed > chrono::Utc::now().naive_utc() - TimeDelta::new(*crate::STUCK_WORKING_AFTER_SECS, 0).unwrap() => {
+                                    tracing::warn!("Generation {uuid_job_id} is already being worked on, dropping the duplicate");
+                                    return Ok(Err(GenerationError::DeleteImmediately));
+                                },
+                                // Working this long means an earlier attempt died part way through, it still counts
+                                GenerationStatus::Working | GenerationStatus::Waiting => (),
+                            }
+                            // A job that keeps dying before it can fail itself, such as one that panics, would
+                            // otherwise be handed out forever
+                            if attempts >= *crate::MAX_ATTEMPTS {
+                                return Ok(Err(GenerationError::TooManyAttempts(attempts)));
+                            }
+                            let _ = diesel::update(generation::table.filter(generation::userid.eq(generate_options.user_id).and(generation::jobid.eq(uuid_job_id))))
+                                                .set((
+                                                    generation::status.eq(GenerationStatus::Working),
+                                                    generation::attempts.eq(generation::attempts + 1),
+                                                    generation::lastchanged.eq(chrono::Utc::now().naive_utc()),
+                                                ))
+                                                .execute(conn)
+                                                .await?;
+                            Ok(Ok(()))
+                        }.scope_boxed())
+                        .await
+                        .map_err(|err| {
+                            tracing::error!("Transaction error: {err}");
+                            GenerationError::PostgresCommandFailure
+                        })?;
+        if let Err(err) = ret {
+            return Err(err);
+        }
+    }
+    report_progress(&appstate, uuid_job_id, GenerationStatus::Working, 0).await;
+
+    let recent_fingerprints: HashSet<String>;
+    let mut history: Vec<MarkedAnswer> = Vec::new();
+    {
+        let mut postgres_conn = appstate.postgres.get()
+                                .await.map_err(|err| {
+                                    tracing::error!("Failed to open postgres connection, {err}");
+                                    GenerationError::PostgresConnectionFailure
+                                })?;
+        let recent: Vec<Option<String>> = generation::table.filter(generation::userid.eq(generate_options.user_id).and(generation::status.eq(GenerationStatus::Success)))
+                                            .order(generation::createdat.desc())
+                                            .limit(*crate::FINGERPRINT_WINDOW)
+                                            .select(generation::fingerprints)
+                                            .load(&mut postgres_conn)
+                                            .await.map_err(|err| {
+                                                tracing::error!("Failed to query recent fingerprints: {err}");
+                                                GenerationError::PostgresCommandFailure
+                                            })?;
+        recent_fingerprints = recent.iter()
+                                .flatten()
+                                .flat_map(|fingerprints| fingerprints.split(','))
+                                .map(str::to_owned)
+                                .collect();
+
+        if generate_options.personalised {
+            let answers: Vec<(String, String, i16, i16, NaiveDateTime)> = markedanswers::table.filter(markedanswers::userid.eq(generate_options.user_id))
+                                            .order(markedanswers::answeredat.desc())
+                                            .limit(*crate::HISTORY_WINDOW)
+                                            .select((markedanswers::templateid, markedanswers::topic, markedanswers::marksawarded, markedanswers::marksavailable, markedanswers::answeredat))
+                                            .load(&mut postgres_conn)
+                                            .await.map_err(|err| {
+                                                tracing::error!("Failed to query marked answers: {err}");
+                                                GenerationError::PostgresCommandFailure
+                                            })?;
+            // Topics that have since been renamed or removed are dropped rather than failing the paper
+            history = answers.into_iter()
+                        .filter_map(|(template_id, topic, awarded, available, answered_on)| Some(MarkedAnswer {
+                            template_id,
+                            topic: topic.parse::<GenerateOption>().ok()?,
+                            awarded: awarded.max(0) as u32,
+                            available: available.max(0) as u32,
+                            answered_on,
+                        }))
+                        .collect();
+        }
+    }
+
+    report_progress(&appstate, uuid_job_id, GenerationStatus::Working, 20).await;
+
+    let personalised = generate_options.personalised;
+    let mut paper = paper::Paper::new(generate_options.user_id, generate_options.gen_id, generate_options.opts, generate_options.composition)
+                        .with_locale(generate_options.locale);
+    let population_result = if personalised {
+        paper.populate_personalised(&history, &recent_fingerprints)
+    } else {
+        paper.populate_avoiding(&recent_fingerprints)
+    };
+
+    match population_result {
+        Ok(()) => (),
+        Err(failure) => return Err(GenerationError::InternalGenerationFailure(failure)),
+    }
+
+    report_progress(&appstate, uuid_job_id, GenerationStatus::Working, 60).await;
+
+    let serialize_gzip_buf = match blob::encode(&paper) {
+        Ok(buf) => buf,
+        Err(BlobError::CompressionError) => {
+            tracing::error!("Failed to compress serialised paper");
+            return Err(GenerationError::CompressionError);
+        },
+        Err(err) => {
+            tracing::error!("Failed to serialise paper due to: {err:?}");
+            return Err(GenerationError::SerializeError);
+        },
+    };
+    
+    if let Err(put_err) = paper_store.put(uuid_job_id, serialize_gzip_buf).await {
+        tracing::error!("Failed to store serialised paper due to {put_err}");
+        return Err(GenerationError::PaperStorePutError);
+    }
+    report_progress(&appstate, uuid_job_id, GenerationStatus::Working, 90).await;
+    
+    let finished_on = chrono::Utc::now().naive_utc();
+    let notify_by_email: bool;
+
+    {
+        let mut postgres_conn = appstate.postgres.get()
+                                .await.map_err(|err| {
+                                    tracing::error!("Failed to open postgres connection, {err}");
+                                    GenerationError::PostgresConnectionFailure
+                                })?;
+
+        // Personalised rows are inserted without options, record the topics that were picked
+        if personalised {
+            let options = paper.generated_options().iter().map(ToString::to_string).collect::<Vec<String>>().join(",");
+            let _ = diesel::update(generation::table.filter(generation::jobid.eq(uuid_job_id)))
+                        .set(generation::options.eq(options))
+                        .execute(&mut postgres_conn)
+                        .await.map_err(|err| {
+                                    tracing::error!("Failed to record personalised options: {}", err);
+                                    GenerationError::PostgresCommandFailure
+                                })?;
+        }
+        notify_by_email = diesel::update(generation::table.filter(generation::jobid.eq(uuid_job_id)))
+                    .set((
+                            generation::status.eq(GenerationStatus::Success),
+                            generation::finishedon.eq(finished_on),
+                            generation::lastchanged.eq(finished_on),
+                            generation::fingerprints.eq(paper.fingerprints().join(",")),
+                    ))
+                    .returning(generation::notifybyemail)
+                    .get_result::<bool>(&mut postgres_conn)
+                    .await.map_err(|err| {
+                                tracing::error!("Insert postgres failure: {}", err);
+                                GenerationError::PostgresCommandFailure
+                            })?;
+    }
+    // The row is what makes the paper ready, so the email doesn't wait on Redis
+    if notify_by_email {
+        notify_ready(&appstate, &lambda_client, generate_options.user_id, uuid_job_id).await;
+    }
+    // The paper is stored and the row says so, the cache is only a shortcut for status checks so failing
+    // here can't turn the job into a failure
+    match appstate.redis.get().await {
+        Ok(mut redis_conn) => {
+            let generate_redis_key = format!("gen:job:{}", generate_options.job_id);
+            if let Err(err) = cmd("SET")
+                .arg(&[&generate_redis_key, "Success", "EX", "240"])
+                .query_async::<_, ()>(&mut redis_conn)
+                .await
+            {
+                tracing::error!("Redis set command failed to flag as success, {:?}", err);
+            }
+            if let Err(err) = Progress::publish(&mut redis_conn, uuid_job_id, &ProgressEvent::new(GenerationStatus::Success, 100)).await {
+                tracing::warn!("Failed to publish progress for {uuid_job_id}, {:?}", err);
+            }
+        },
+        Err(err) => tracing::error!("Failed to open redis connection to flag as success, {err}"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,3 @@
+// Question generation engine, shared between the lambda and anything else that needs to build papers
+
+pub mod generate;
@@ -1,67 +1,188 @@
-// Entry point for lambda
+// Entry point for lambda, or a long running worker that polls the job queue when WORKER_MODE is "poll"
 
-use ::std::sync::Arc;
+use ::std::{sync::Arc, time::Duration};
 use aws_config::BehaviorVersion;
-use aws_lambda_events::event::sqs::{SqsEvent, SqsMessage};
+use aws_lambda_events::event::sqs::{BatchItemFailure, SqsBatchResponse, SqsEvent};
 use lambda_runtime::{service_fn, Error as LambdaError, LambdaEvent};
 use lazy_static::lazy_static;
 use common_types::Generate::SQSBody;
-use common_types_accounts::Schema::{generation, hooked_sql_types::GenerationStatus};
+use common_types_accounts::{
+    Constants,
+    Credits::{clear_cached_credits, refund_failed_generation},
+    DB::InsertableDeadLetter,
+    Progress::{self, ProgressEvent},
+    Queue::{self, JobQueue},
+    Storage::{self, PaperStore},
+    Schema::{generation, generationdeadletters, hooked_sql_types::GenerationStatus},
+};
 use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
 use diesel_async::RunQueryDsl;
 use deadpool_redis::redis::cmd;
+use tokio::sync::Semaphore;
 
-mod generate;
-use generate::GenerationError;
+mod job;
+use job::GenerationError;
 
 lazy_static!{
-    static ref GENERATE_QUEUE_URL: String = {
-        dotenvy::var("GENERATE_QUEUE_URL").expect("No environment variable for GENERATE_QUEUE_URL").to_owned()
+    // How many of the user's previous generations are checked for repeated questions
+    static ref FINGERPRINT_WINDOW: i64 = {
+        let mut window = 10;
+        if let Ok(count) = dotenvy::var("FINGERPRINT_WINDOW") {
+            if let Ok(new_window) = count.parse() {
+                window = new_window;
+                tracing::info!("Using custom FINGERPRINT_WINDOW: {window}");
+            } else {
+                tracing::info!("Failed to parse FINGERPRINT_WINDOW, using default, {window}");
+            }
+        }
+        window
+    };
+    // How many times a job is tried before it is failed and dead lettered
+    static ref MAX_ATTEMPTS: i32 = {
+        let mut attempts = 5;
+        if let Ok(count) = dotenvy::var("MAX_ATTEMPTS") {
+            match count.parse() {
+                Ok(new_attempts) if new_attempts > 0 => {
+                    attempts = new_attempts;
+                    tracing::info!("Using custom MAX_ATTEMPTS: {attempts}");
+                },
+                _ => tracing::info!("Failed to parse MAX_ATTEMPTS, using default, {attempts}"),
+            }
+        }
+        attempts
+    };
+    // Should match the reaper, a Working job that changed more recently than this is still being generated elsewhere
+    static ref STUCK_WORKING_AFTER_SECS: i64 = {
+        let mut secs = 60 * 30;
+        if let Ok(count) = dotenvy::var("STUCK_WORKING_AFTER_SECS") {
+            match count.parse() {
+                Ok(new_secs) if new_secs > 0 => {
+                    secs = new_secs;
+                    tracing::info!("Using custom STUCK_WORKING_AFTER_SECS: {secs}");
+                },
+                _ => tracing::info!("Failed to parse STUCK_WORKING_AFTER_SECS, using default, {secs}"),
+            }
+        }
+        secs
+    };
+    // How many records of a batch are generated at the same time
+    static ref MAX_CONCURRENT_JOBS: usize = {
+        let mut limit = 4;
+        if let Ok(count) = dotenvy::var("MAX_CONCURRENT_JOBS") {
+            match count.parse() {
+                Ok(new_limit) if new_limit > 0 => {
+                    limit = new_limit;
+                    tracing::info!("Using custom MAX_CONCURRENT_JOBS: {limit}");
+                },
+                _ => tracing::info!("Failed to parse MAX_CONCURRENT_JOBS, using default, {limit}"),
+            }
+        }
+        limit
+    };
+    // How long the worker waits before polling again when the queue was empty
+    static ref POLL_INTERVAL_MS: u64 = {
+        let mut interval = 1000;
+        if let Ok(ms) = dotenvy::var("POLL_INTERVAL_MS") {
+            if let Ok(new_interval) = ms.parse() {
+                interval = new_interval;
+                tracing::info!("Using custom POLL_INTERVAL_MS: {interval}");
+            } else {
+                tracing::info!("Failed to parse POLL_INTERVAL_MS, using default, {interval}");
+            }
+        }
+        interval
     };
-    static ref GENERATED_BUCKET_NAME: String = {
-        dotenvy::var("GENERATED_BUCKET_NAME").expect("No environment variable for GENERATED_BUCKET_NAME").to_owned()
+    // How many of the user's most recent marked answers personalised papers are picked from
+    static ref HISTORY_WINDOW: i64 = {
+        let mut window = 500;
+        if let Ok(count) = dotenvy::var("HISTORY_WINDOW") {
+            if let Ok(new_window) = count.parse() {
+                window = new_window;
+                tracing::info!("Using custom HISTORY_WINDOW: {window}");
+            } else {
+                tracing::info!("Failed to parse HISTORY_WINDOW, using default, {window}");
+            }
+        }
+        window
     };
 }
 
-async fn delete_message(sqs_client: Arc<aws_sdk_sqs::Client>, record: &SqsMessage) -> Result<(), LambdaError> {
-    if let Some(ref receipt_handle) = record.receipt_handle {
-                    let _ = sqs_client
-                        .delete_message()
-                        .queue_url(&*GENERATE_QUEUE_URL)
-                        .receipt_handle(receipt_handle)
-                        .send()
-                        .await?;
-    }
-    Ok(())
-}
-async fn delete_from_receipt(sqs_client: Arc<aws_sdk_sqs::Client>, receipt_handle: String) -> Result<(), LambdaError> {
-    let _ = sqs_client
-        .delete_message()
-        .queue_url(&*GENERATE_QUEUE_URL)
-        .receipt_handle(receipt_handle)
-        .send()
-        .await?;
-    Ok(())
+enum FailOutcome {
+    Missing,
+    // The paper was stored before whatever went wrong, it stays a success
+    Finished,
+    Retry,
+    // How many credits were refunded, if any
+    Failed(Option<i32>),
 }
 
-async fn flag_as_failure(appstate: common_types_accounts::MinimalState::AppState, jobid: String) -> bool {
-    let uuid_job_id = uuid::Uuid::try_parse(&jobid);
-    let Ok(uuid_job_id) = uuid_job_id else {
-        return false; 
+// Marks the job as failed, refunds its credits and keeps the record it came from so an admin can requeue it, unless
+// the failure is transient and the job still has attempts left
+// Returns true when the record should be left on the queue and tried again later
+async fn fail_job(appstate: common_types_accounts::MinimalState::AppState, raw_body: &str, job_id: &str, user_id: i64, err: GenerationError, receive_count: i32) -> bool {
+    let Ok(uuid_job_id) = uuid::Uuid::try_parse(job_id) else {
+        return false;
     };
-    let postgres_conn = appstate.postgres.get()
-                                .await;
-    let Ok(mut postgres_conn) = postgres_conn else {
+    let Ok(mut postgres_conn) = appstate.postgres.get().await else {
         return true; // try again later
     };
-    match diesel::update(generation::table.filter(generation::jobid.eq(uuid_job_id)))
-        .set(generation::status.eq(GenerationStatus::Failed))
-        .execute(&mut postgres_conn)
-        .await
-    {
-        Ok(_) => {
+    let transient = err.is_transient();
+    let reason = err.reason();
+    let failed = postgres_conn.build_transaction()
+                    .read_write()
+                    .run::<FailOutcome, diesel::result::Error, _>(|conn| async move {
+                        let Some((status, attempts)) = generation::table.filter(generation::jobid.eq(uuid_job_id))
+                                            .select((generation::status, generation::attempts))
+                                            .for_update()
+                                            .first::<(GenerationStatus, i32)>(conn)
+                                            .await
+                                            .optional()? else {
+                            return Ok(FailOutcome::Missing);
+                        };
+                        if status == GenerationStatus::Success {
+                            return Ok(FailOutcome::Finished);
+                        }
+                        // The receive count resets when a job is sent again, the column doesn't
+                        let attempts = attempts.max(receive_count);
+                        if transient && attempts < *MAX_ATTEMPTS {
+                            return Ok(FailOutcome::Retry);
+                        }
+                        diesel::update(generation::table.filter(generation::jobid.eq(uuid_job_id).and(generation::status.ne(GenerationStatus::Success))))
+                            .set((
+                                generation::status.eq(GenerationStatus::Failed),
+                                generation::lastchanged.eq(chrono::Utc::now().naive_utc()),
+                                generation::attempts.eq(attempts),
+                                generation::failurereason.eq(&reason),
+                            ))
+                            .execute(conn)
+                            .await?;
+                        diesel::insert_into(generationdeadletters::table)
+                            .values(&InsertableDeadLetter {
+                                jobid: uuid_job_id,
+                                userid: user_id,
+                                body: raw_body,
+                                reason: &reason,
+                                attempts,
+                                createdat: chrono::Utc::now().naive_utc(),
+                            })
+                            .execute(conn)
+                            .await?;
+                        let refund = refund_failed_generation(conn, uuid_job_id, *Constants::STANDARD_CREDITS_EXPIRE_AFTER_SECS).await?;
+                        Ok(FailOutcome::Failed(refund.map(|(_, credits)| credits)))
+                    }.scope_boxed())
+                    .await;
+    match failed {
+        Ok(FailOutcome::Failed(refunded)) => {
+            tracing::error!("Generation {uuid_job_id} failed and was dead lettered");
             match appstate.redis.get().await {
                 Ok(mut redis_conn) => {
+                    if let Some(credits) = refunded {
+                        tracing::info!("Refunded {credits} credits to {user_id} for {uuid_job_id}");
+                        if let Err(err) = clear_cached_credits(&mut redis_conn, user_id).await {
+                            tracing::error!("Failed to clear cached credits for {user_id} after refunding, {:?}", err);
+                        }
+                    }
                     let generate_redis_key = format!("gen:job:{uuid_job_id}");
                     if let Err(err) = cmd("SET")
                         .arg(&[&generate_redis_key, "Failed", "EX", "120"])
@@ -71,11 +192,23 @@ async fn flag_as_failure(appstate: common_types_accounts::MinimalState::AppState
                         tracing::error!("Redis set command failed to flag as failure but won't try again, {:?}", err);
                         // We won't retry though!
                     }
+                    if let Err(err) = Progress::publish(&mut redis_conn, uuid_job_id, &ProgressEvent::new(GenerationStatus::Failed, 0)).await {
+                        tracing::warn!("Failed to publish progress for {uuid_job_id}, {:?}", err);
+                    }
                 },
                 Err(err) => tracing::error!("Failed to get redis connection, won't try again!, {:?}", err),
             }
             false
         },
+        Ok(FailOutcome::Retry) => true,
+        Ok(FailOutcome::Missing) => {
+            tracing::warn!("Generation {uuid_job_id} no longer exists, dropping it");
+            false
+        },
+        Ok(FailOutcome::Finished) => {
+            tracing::warn!("Generation {uuid_job_id} already succeeded, not failing it");
+            false
+        },
         Err(err) => {
             tracing::error!("Failed to update generation record to failure, due to {err}, will try again later, {uuid_job_id}");
             true // try again later
@@ -83,70 +216,139 @@ async fn flag_as_failure(appstate: common_types_accounts::MinimalState::AppState
     }
 }
 
-#[tracing::instrument(skip(appstate, sqs_client, s3_client, event), fields(req_id = %event.context.request_id))]
+// Returns true when the record should be left on the queue and tried again later
+async fn process_record(
+    appstate: common_types_accounts::MinimalState::AppState,
+    paper_store: Arc<dyn PaperStore>,
+    lambda_client: aws_sdk_lambda::Client,
+    body: &str,
+    receive_count: i32,
+) -> bool {
+    let Ok(generate_options) = serde_json::from_str::<SQSBody>(body) else {
+        tracing::error!("Failed to deserialize body: {}", body);
+        return false;
+    };
+    let job_id = generate_options.job_id.clone();
+    let user_id = generate_options.user_id;
+    // Run on its own task so a panic fails the job instead of it being handed out again
+    let generation = tokio::spawn(job::generate(appstate.clone(), paper_store, lambda_client, generate_options)).await.unwrap_or_else(|err| {
+        tracing::error!("Generation {job_id} panicked, {err}");
+        Err(GenerationError::Panicked)
+    });
+    match generation {
+        Ok(()) => false,
+        Err(GenerationError::DeleteImmediately) => false,
+        Err(err) => {
+            if let GenerationError::InternalGenerationFailure(ref failure) = err {
+                tracing::error!("Failed to generate due to {:?}", failure);
+            }
+            fail_job(appstate, body, &job_id, user_id, err, receive_count).await
+        }
+    }
+}
+
+#[tracing::instrument(skip(appstate, paper_store, lambda_client, event), fields(req_id = %event.context.request_id))]
 async fn handler(
     appstate: common_types_accounts::MinimalState::AppState,
-    sqs_client: Arc<aws_sdk_sqs::Client>,
-    s3_client: Arc<aws_sdk_s3::Client>,
+    paper_store: Arc<dyn PaperStore>,
+    lambda_client: aws_sdk_lambda::Client,
     event: LambdaEvent<SqsEvent>,
-) -> Result<(), LambdaError> {
-    for record in event.payload.records.iter() {
-        // process the record
-        if let Some(body) = &record.body {
-            if let (Ok(body), Some(ref receipt)) = (serde_json::from_str::<SQSBody>(body), &record.receipt_handle) {
-                let handle : tokio::task::JoinHandle<Result<(), LambdaError>>;
-                {
-                    let receipt = receipt.clone();
-                    let appstate = appstate.clone();
-                    let sqs_client = sqs_client.clone();
-                    let s3_client = s3_client.clone();
-                    handle = tokio::spawn(async move {
-                        let job_id = body.job_id.clone();
-                        let result = generate::generate(appstate.clone(), s3_client, body).await;
-                        match result {
-                            Ok(()) => {
-                                delete_from_receipt(sqs_client.clone(), receipt).await?;
-                                Ok(())
-                            },
-                            Err(err) => {
-                                use GenerationError::*;
-                                match err {
-                                    DeleteImmediately => (), // do nothing and let it be deleted
-                                                             // from queue
-                                    RedisConnectionFailure | PostgresConnectionFailure | PostgresCommandFailure => return Ok(()), // dont delete if it reaches this
-                                    InternalGenerationFailure(failure) => {
-                                        tracing::error!("Failed to generate due to {:?}", failure);
-                                        if flag_as_failure(appstate.clone(), job_id).await {
-                                            return Ok(()) // if returns true then we wont delete
-                                                          // message and will try again later
-                                        }
-                                    },
-                                    _ => {
-                                        if flag_as_failure(appstate.clone(), job_id).await {
-                                            return Ok(()) // if returns true then we wont delete
-                                                          // message and will try again later
-                                        }
-                                    },
-                                }
-                                delete_from_receipt(sqs_client.clone(), receipt).await?;
-                                Ok(())
-                            }
-                        }
-                    });
+) -> Result<SqsBatchResponse, LambdaError> {
+    let permits = Arc::new(Semaphore::new(*MAX_CONCURRENT_JOBS));
+    let handles = event.payload.records.into_iter().map(|record| {
+        let permits = permits.clone();
+        let appstate = appstate.clone();
+        let paper_store = paper_store.clone();
+        let lambda_client = lambda_client.clone();
+        (record.message_id.clone(), tokio::spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let Some(body) = &record.body else {
+                tracing::warn!("Empty body encountered in record");
+                return false;
+            };
+            let receive_count = record.attributes.get("ApproximateReceiveCount")
+                                    .and_then(|count| count.parse().ok())
+                                    .unwrap_or(1);
+            process_record(appstate, paper_store, lambda_client, body, receive_count).await
+        }))
+    }).collect::<Vec<_>>();
+
+    // Only the records reported here go back on the queue, everything else is deleted by lambda
+    let mut batch_item_failures = Vec::new();
+    for (message_id, handle) in handles {
+        // Generation panics are already failed by process_record, anything else is left for the queue to hand out again
+        if !handle.await.unwrap_or(true) {
+            continue;
+        }
+        match message_id {
+            Some(item_identifier) => batch_item_failures.push(BatchItemFailure { item_identifier }),
+            None => tracing::error!("Record without a message id can't be retried"),
+        }
+    }
+    Ok(SqsBatchResponse { batch_item_failures })
+}
+
+// Receives jobs for as long as there is room for them, a job that isn't acked is handed out again by the queue
+async fn poll(
+    appstate: common_types_accounts::MinimalState::AppState,
+    paper_store: Arc<dyn PaperStore>,
+    lambda_client: aws_sdk_lambda::Client,
+    job_queue: Arc<dyn JobQueue>,
+    permits: Arc<Semaphore>,
+) {
+    loop {
+        let permit = permits.clone().acquire_owned().await.expect("Job permits are never closed");
+        let jobs = match job_queue.receive(permits.available_permits() + 1).await {
+            Ok(jobs) => jobs,
+            Err(err) => {
+                tracing::error!("Failed to receive jobs, {err}");
+                Vec::new()
+            },
+        };
+        if jobs.is_empty() {
+            drop(permit);
+            tokio::time::sleep(Duration::from_millis(*POLL_INTERVAL_MS)).await;
+            continue;
+        }
+        let mut permit = Some(permit);
+        for job in jobs {
+            let permit = match permit.take() {
+                Some(permit) => permit,
+                None => permits.clone().acquire_owned().await.expect("Job permits are never closed"),
+            };
+            let appstate = appstate.clone();
+            let paper_store = paper_store.clone();
+            let lambda_client = lambda_client.clone();
+            let job_queue = job_queue.clone();
+            tokio::spawn(async move {
+                let _permit = permit;
+                if process_record(appstate, paper_store, lambda_client, &job.body, job.receive_count).await {
+                    return;
                 }
-                /* dnc about errors lol */
-                let _ = handle.await;
-            } else {
-                tracing::error!("Failed to deserialize body: {}", body);
-                delete_message(sqs_client.clone(), record).await?;
-            }
-        } else {
-            tracing::warn!("Empty body encountered in record");
-            delete_message(sqs_client.clone(), record).await?;
+                if let Err(err) = job_queue.ack(&job).await {
+                    tracing::error!("Failed to ack job, it will be handed out again, {err}");
+                }
+            });
         }
     }
-    Ok(())
+}
 
+async fn run_worker(
+    appstate: common_types_accounts::MinimalState::AppState,
+    paper_store: Arc<dyn PaperStore>,
+    lambda_client: aws_sdk_lambda::Client,
+    job_queue: Arc<dyn JobQueue>,
+) -> Result<(), LambdaError> {
+    tracing::info!("Polling for generation jobs");
+    let permits = Arc::new(Semaphore::new(*MAX_CONCURRENT_JOBS));
+    tokio::select! {
+        _ = poll(appstate, paper_store, lambda_client, job_queue, permits.clone()) => (),
+        result = tokio::signal::ctrl_c() => result?,
+    }
+    // Jobs already started are finished rather than left to time out
+    tracing::info!("Shutting down, waiting for running jobs");
+    let _ = permits.acquire_many(*MAX_CONCURRENT_JOBS as u32).await;
+    Ok(())
 }
 
 #[tokio::main]
@@ -158,13 +360,18 @@ async fn main() -> Result<(), LambdaError> {
         .init();
 
     let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let s3_client = Arc::new(aws_sdk_s3::Client::new(&config));
-    let sqs_client = Arc::new(aws_sdk_sqs::Client::new(&config));
+    let paper_store = Storage::from_config(&config);
+    let lambda_client = aws_sdk_lambda::Client::new(&config);
 
     let appstate = common_types_accounts::MinimalState::make_state().await?;
 
+    if dotenvy::var("WORKER_MODE").is_ok_and(|mode| mode == "poll") {
+        let job_queue = Queue::from_config(&config, appstate.postgres.clone());
+        return run_worker(appstate, paper_store, lambda_client, job_queue).await;
+    }
+
     lambda_runtime::run(service_fn(|event: LambdaEvent<SqsEvent>| async {
-        handler(appstate.clone(), sqs_client.clone(), s3_client.clone(), event).await
+        handler(appstate.clone(), paper_store.clone(), lambda_client.clone(), event).await
     }))
     .await
 }
@@ -0,0 +1,95 @@
+// Decodes papers written by older generators, checked in under tests/fixtures. paper.v0 is bare gzipped
+// msgpack from before the RPDL frame and paper.v1 is framed schema version 1, before fingerprints,
+// question metadata, header diagrams and the paper locale were added.
+
+use ::std::{fs, io::Read, path::PathBuf};
+use flate2::read::GzDecoder;
+use common_types::Generate::{GenerateId, GenerateOption, Locale};
+use aws_lambda_generate::generate::{
+    paper::blob::{self, BlobHeader, SCHEMA_VERSION},
+    question::{Misconception, QuestionType},
+};
+use common_types_accounts::Routes::generated::content::strip_blob_frame;
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name);
+    fs::read(&path).unwrap_or_else(|_| panic!("Missing fixture {}", path.display()))
+}
+
+fn gunzip(gzipped: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    GzDecoder::new(gzipped).read_to_end(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn decodes_unframed_v0_papers() {
+    let (header, paper) = blob::decode(&fixture("paper.v0.rapidl.gz")).unwrap();
+    assert_eq!(header, BlobHeader { schema_version: 0, generator_version: None });
+
+    assert_eq!(paper.created_on().to_string(), "2024-03-01 09:30:00");
+    assert!(matches!(paper.generated_catagory(), Some(GenerateId::MathsMechanics)));
+    assert_eq!(paper.generated_options(), &[GenerateOption::SUVAT]);
+    // Nothing to carry these over from
+    assert_eq!(paper.total_marks(), 0);
+    assert!(paper.fingerprints().is_empty());
+    assert_eq!(paper.locale(), Locale::EnGb);
+
+    let [QuestionType::Single(first), QuestionType::Grouped(header, parts)] = paper.questions() else {
+        panic!("v0 paper migrated to the wrong questions");
+    };
+    assert_eq!(first.header.raw_text, "A ball is thrown.");
+    assert_eq!(first.latex_text, r"Find \(u\).");
+    assert_eq!(first.mark_scheme.raw_text, "u = 4");
+    assert_eq!(first.marks, 0);
+    assert!(first.metadata.is_none());
+    assert_eq!(header.raw_text, "A car accelerates.");
+    let [QuestionType::Single(part)] = parts.as_slice() else {
+        panic!("v0 grouped question lost its part");
+    };
+    assert_eq!(part.mark_scheme.latex_text, r"\(s=10\)");
+}
+
+#[test]
+fn decodes_framed_v1_papers() {
+    let (header, paper) = blob::decode(&fixture("paper.v1.rapidl.gz")).unwrap();
+    assert_eq!(header, BlobHeader { schema_version: 1, generator_version: Some("0.1.0".to_string()) });
+
+    assert_eq!(paper.generated_options(), &[GenerateOption::SUVAT]);
+    assert_eq!(paper.total_marks(), 3);
+    assert_eq!(paper.suggested_minutes(), 5);
+    // Added after v1, so they default
+    assert!(paper.fingerprints().is_empty());
+    assert_eq!(paper.locale(), Locale::EnGb);
+
+    let [QuestionType::MultipleChoice(question, choices), QuestionType::Grouped(_, parts)] = paper.questions() else {
+        panic!("v1 paper decoded to the wrong questions");
+    };
+    assert_eq!(question.marks, 1);
+    assert!(question.metadata.is_none());
+    assert!(question.header.diagram.is_none());
+    assert_eq!(choices.iter().filter_map(|choice| choice.misconception).collect::<Vec<Misconception>>(), [Misconception::SignError]);
+    assert_eq!(parts.iter().map(QuestionType::marks).sum::<u32>(), 2);
+}
+
+#[test]
+fn reencodes_migrated_papers_at_the_current_version() {
+    let (_, paper) = blob::decode(&fixture("paper.v1.rapidl.gz")).unwrap();
+    let (header, reencoded) = blob::decode(&blob::encode(&paper).unwrap()).unwrap();
+    assert_eq!(header.schema_version, SCHEMA_VERSION);
+    assert_eq!(reencoded.total_marks(), paper.total_marks());
+    assert_eq!(reencoded.questions().len(), paper.questions().len());
+}
+
+// GET /generated/content hands old clients the msgpack without the frame
+#[test]
+fn strips_the_frame_for_old_clients() {
+    let unframed = fixture("paper.v0.rapidl.gz");
+    assert_eq!(strip_blob_frame(&unframed).unwrap(), unframed);
+
+    let framed = fixture("paper.v1.rapidl.gz");
+    let stripped = gunzip(&strip_blob_frame(&framed).unwrap());
+    // "RPDL", the u16 schema version, the length byte and "0.1.0"
+    assert_eq!(stripped, gunzip(&framed)[12..]);
+    assert!(!stripped.starts_with(blob::MAGIC));
+}
@@ -0,0 +1,351 @@
+// Runs every registered template over a spread of seeds and checks the output looks like
+// something we'd be happy to put in front of a student. Snapshots for a few fixed seeds live in
+// tests/snapshots, set UPDATE_SNAPSHOTS=1 to rewrite them after an intended template change.
+
+use ::std::{fs, panic, path::PathBuf};
+use common_types::Generate::Locale;
+use aws_lambda_generate::generate::{
+    engine::{self, registry::{self, Template}},
+    locale,
+    oncelabel::{LabelError, OnceLabel},
+    question::{Choice, MarkScheme, Misconception, Question, QuestionHeader, QuestionType},
+};
+
+const SEEDS: u64 = 2000;
+const SNAPSHOT_SEEDS: [u64; 3] = [0, 1, 42];
+// Other locales share the numbers with en-GB, so fewer seeds are enough to cover their wording
+const LOCALE_SEEDS: u64 = 200;
+const LOCALES: [Locale; 2] = [Locale::EnGb, Locale::Cy];
+// Nothing a template asks for should get anywhere near this
+const ANSWER_BOUND: f64 = 1_000_000.0;
+
+fn generate(template: &Template, seed: u64) -> Vec<QuestionType> {
+    let result = panic::catch_unwind(|| engine::run(template, seed, None).consume_get_questions());
+    match result {
+        Ok(questions) => questions,
+        Err(_) => panic!("{} panicked with seed {seed}", template.id),
+    }
+}
+
+// Every piece of text in the tree, tagged with where it came from for the failure message
+fn texts(questions: &[QuestionType]) -> Vec<(&'static str, &str)> {
+    fn push_header<'a>(texts: &mut Vec<(&'static str, &'a str)>, header: &'a QuestionHeader) {
+        texts.push(("header raw", &header.raw_text));
+        texts.push(("header latex", &header.latex_text));
+    }
+    fn push_mark_scheme<'a>(texts: &mut Vec<(&'static str, &'a str)>, mark_scheme: &'a MarkScheme) {
+        texts.push(("mark scheme raw", &mark_scheme.raw_text));
+        texts.push(("mark scheme latex", &mark_scheme.latex_text));
+        for step in mark_scheme.steps.iter() {
+            for text in [&step.explanation, &step.working, &step.result] {
+                texts.push(("step raw", &text.raw_text));
+                texts.push(("step latex", &text.latex_text));
+            }
+        }
+    }
+    fn push_question<'a>(texts: &mut Vec<(&'static str, &'a str)>, question: &'a Question) {
+        push_header(texts, &question.header);
+        texts.push(("question raw", &question.raw_text));
+        texts.push(("question latex", &question.latex_text));
+        push_mark_scheme(texts, &question.mark_scheme);
+    }
+    fn push<'a>(texts: &mut Vec<(&'static str, &'a str)>, questions: &'a [QuestionType]) {
+        for question in questions {
+            match question {
+                QuestionType::Single(question) => push_question(texts, question),
+                QuestionType::MultipleChoice(question, choices) => {
+                    push_question(texts, question);
+                    for choice in choices {
+                        texts.push(("choice raw", &choice.raw_text));
+                        texts.push(("choice latex", &choice.latex_text));
+                    }
+                },
+                QuestionType::Grouped(header, questions) => {
+                    push_header(texts, header);
+                    push(texts, questions);
+                },
+            }
+        }
+    }
+    let mut texts = Vec::new();
+    push(&mut texts, questions);
+    texts
+}
+
+fn leaves(questions: &[QuestionType]) -> Vec<(&Question, Option<&[Choice]>)> {
+    let mut leaves = Vec::new();
+    for question in questions {
+        match question {
+            QuestionType::Single(question) => leaves.push((question, None)),
+            QuestionType::MultipleChoice(question, choices) => leaves.push((question, Some(choices.as_slice()))),
+            QuestionType::Grouped(_, questions) => leaves.append(&mut self::leaves(questions)),
+        }
+    }
+    leaves
+}
+
+// Numeric literals in the text, a leading - counts as part of the number
+fn numbers(text: &str) -> Vec<(&str, f64)> {
+    let bytes = text.as_bytes();
+    let mut numbers = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index].is_ascii_digit() {
+            let start = if index > 0 && bytes[index - 1] == b'-' { index - 1 } else { index };
+            let mut end = index;
+            while end < bytes.len() && (bytes[end].is_ascii_digit() || bytes[end] == b'.') {
+                end += 1;
+            }
+            let literal = text[start..end].trim_end_matches('.');
+            if let Ok(value) = literal.parse::<f64>() {
+                numbers.push((literal, value));
+            }
+            index = end;
+        } else {
+            index += 1;
+        }
+    }
+    numbers
+}
+
+fn has_non_finite(text: &str) -> bool {
+    text.contains("NaN") || text.match_indices("inf").any(|(index, _)| index == 0 || text.as_bytes()[index - 1] != b'\\')
+}
+
+fn has_negative_zero(text: &str) -> bool {
+    numbers(text).into_iter().any(|(literal, value)| literal.starts_with('-') && value == 0.0)
+}
+
+// Inline math opens and closes in order and is never nested
+fn math_delimiters_balanced(text: &str) -> bool {
+    let mut open = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            continue;
+        }
+        match chars.next() {
+            Some('(') if !open => open = true,
+            Some(')') if open => open = false,
+            Some('(') | Some(')') => return false,
+            _ => (),
+        }
+    }
+    !open
+}
+
+// Not a full TeX parser, but catches what templates get wrong: unbalanced braces, \begin
+// without a matching \end, and math mode environments used outside of math mode
+fn latex_parses(text: &str) -> Result<(), String> {
+    let mut braces = 0i32;
+    let mut environments: Vec<String> = Vec::new();
+    let mut in_math = false;
+    let bytes = text.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => {
+                let rest = &text[index + 1..];
+                if rest.starts_with('(') {
+                    in_math = true;
+                } else if rest.starts_with(')') {
+                    in_math = false;
+                } else if let Some(name) = rest.strip_prefix("begin{").and_then(|rest| rest.split_once('}')).map(|(name, _)| name) {
+                    if !in_math && name.ends_with("matrix") {
+                        return Err(format!("{name} used outside of math mode"));
+                    }
+                    environments.push(name.to_string());
+                } else if let Some(name) = rest.strip_prefix("end{").and_then(|rest| rest.split_once('}')).map(|(name, _)| name) {
+                    match environments.pop() {
+                        Some(open) if open == name => (),
+                        open => return Err(format!("\\end{{{name}}} closes {open:?}")),
+                    }
+                }
+                // Skip whatever is escaped so \{ and \} don't count as braces
+                index += 2;
+                continue;
+            },
+            b'{' => braces += 1,
+            b'}' => {
+                braces -= 1;
+                if braces < 0 {
+                    return Err(String::from("unmatched }"));
+                }
+            },
+            _ => (),
+        }
+        index += 1;
+    }
+    if braces != 0 {
+        return Err(format!("{braces} unclosed {{"));
+    }
+    if let Some(open) = environments.pop() {
+        return Err(format!("\\begin{{{open}}} never closed"));
+    }
+    Ok(())
+}
+
+fn check(template: &Template, seed: u64, questions: &[QuestionType]) {
+    let context = format!("{} with seed {seed}", template.id);
+    assert!(!questions.is_empty(), "{context} generated no questions");
+
+    for (source, text) in texts(questions) {
+        assert!(!has_non_finite(text), "{context} has a non finite number in {source}: {text}");
+        assert!(!has_negative_zero(text), "{context} has a negative zero in {source}: {text}");
+        for (literal, value) in numbers(text) {
+            assert!(value.abs() < ANSWER_BOUND, "{context} has an out of bounds number {literal} in {source}: {text}");
+        }
+        if source.ends_with("latex") {
+            assert!(math_delimiters_balanced(text), "{context} has unbalanced \\( \\) in {source}: {text}");
+            if let Err(err) = latex_parses(text) {
+                panic!("{context} has LaTeX that doesn't parse in {source} ({err}): {text}");
+            }
+        }
+    }
+
+    let mut total_marks = 0;
+    for (question, choices) in leaves(questions) {
+        let raw = format!("{}{}", question.header.raw_text, question.raw_text);
+        let latex = format!("{}{}", question.header.latex_text, question.latex_text);
+        assert!(!raw.trim().is_empty(), "{context} has a part with no raw text");
+        assert!(!latex.trim().is_empty(), "{context} has a part with no LaTeX text");
+        assert!(!question.mark_scheme.raw_text.trim().is_empty(), "{context} has a part with no raw mark scheme");
+        assert!(!question.mark_scheme.latex_text.trim().is_empty(), "{context} has a part with no LaTeX mark scheme");
+        assert!(question.marks > 0, "{context} has a part worth no marks");
+        assert_eq!(question.marks, question.mark_scheme.total(), "{context} has a part whose marks don't match its mark scheme");
+        total_marks += question.marks;
+
+        let Some(ref metadata) = question.metadata else {
+            panic!("{context} has a part with no metadata");
+        };
+        assert_eq!(metadata.template_id, template.id, "{context} has a part with the wrong template id");
+        assert_eq!(metadata.topic, template.topic, "{context} has a part with the wrong topic");
+        assert_eq!(metadata.seed, seed, "{context} has a part with the wrong seed");
+
+        if let Some(choices) = choices {
+            assert!(choices.len() >= 2, "{context} has a multiple choice part with fewer than two choices");
+            assert_eq!(choices.iter().filter(|choice| choice.is_correct()).count(), 1, "{context} has a multiple choice part without exactly one correct choice");
+            for (index, choice) in choices.iter().enumerate() {
+                assert!(choices[..index].iter().all(|other| other.raw_text != choice.raw_text), "{context} has duplicate choices: {}", choice.raw_text);
+            }
+            let correct = choices.iter().find(|choice| choice.is_correct()).unwrap();
+            for (index, distractor) in choices.iter().enumerate().filter(|(_, choice)| !choice.is_correct()) {
+                assert_ne!(distractor.raw_text, correct.raw_text, "{context} has a {:?} distractor that reads the same as the answer", distractor.misconception);
+                assert!(choices[..index].iter().all(|other| other.misconception != distractor.misconception), "{context} tests for {:?} twice", distractor.misconception);
+            }
+        }
+    }
+    assert!(total_marks <= template.expected_marks as u32, "{context} is worth {total_marks} marks, more than the {} expected", template.expected_marks);
+}
+
+#[test]
+fn every_template_is_registered_once() {
+    let templates = registry::templates().collect::<Vec<&Template>>();
+    assert!(!templates.is_empty());
+    for (index, template) in templates.iter().enumerate() {
+        assert!(templates[..index].iter().all(|other| other.id != template.id), "{} is registered twice", template.id);
+        assert!(template.weight > 0, "{} can never be picked", template.id);
+    }
+}
+
+#[test]
+fn labels_skip_reserved_symbols_until_exhausted() {
+    for template in registry::templates() {
+        let mut oncelabel = OnceLabel::for_template(template);
+        let mut given = Vec::new();
+        while let Ok(label) = oncelabel.next_label() {
+            assert!(!template.reserved_labels.contains(&label.raw), "{} was given its reserved label {}", template.id, label.raw);
+            assert!(!given.contains(&label), "{} was given {} twice", template.id, label.raw);
+            given.push(label);
+        }
+        assert!(!given.is_empty(), "{} has no labels to give out", template.id);
+        assert_eq!(oncelabel.next_label(), Err(LabelError::LabelsExhausted));
+    }
+}
+
+#[test]
+fn templates_hold_invariants_across_seeds() {
+    for template in registry::templates() {
+        for seed in 0..SEEDS {
+            let questions = generate(template, seed);
+            check(template, seed, &questions);
+        }
+    }
+}
+
+#[test]
+fn templates_hold_invariants_in_every_locale() {
+    for locale in LOCALES {
+        locale::set(locale);
+        for template in registry::templates() {
+            for seed in 0..LOCALE_SEEDS {
+                let questions = generate(template, seed);
+                check(template, seed, &questions);
+                for (source, text) in texts(&questions) {
+                    assert!(!text.contains("{$"), "{} with seed {seed} in {locale} has an unfilled placeholder in {source}: {text}", template.id);
+                    assert!(!locale::catalog(Locale::EnGb).contains_key(text), "{} with seed {seed} in {locale} is missing the message {text}", template.id);
+                }
+            }
+        }
+    }
+    locale::set(Locale::EnGb);
+}
+
+#[test]
+fn catalogs_only_translate_known_messages() {
+    for locale in LOCALES {
+        for key in locale::catalog(locale).keys() {
+            assert!(locale::catalog(Locale::EnGb).contains_key(key), "{locale} has {key} which en-GB doesn't");
+        }
+    }
+}
+
+// shuffle_choices keeps every distractor, so a misconception only goes missing if the template stopped offering it
+#[test]
+fn multiple_choice_offers_every_misconception() {
+    use Misconception::*;
+    let template = registry::find("mechanics.suvat.t2").unwrap();
+    for seed in 0..SEEDS {
+        let questions = generate(template, seed);
+        for (part, (_, choices)) in leaves(&questions).into_iter().enumerate() {
+            let choices = choices.unwrap_or_else(|| panic!("{} with seed {seed} has a part that isn't multiple choice", template.id));
+            let offered = choices.iter().filter_map(|choice| choice.misconception).collect::<Vec<Misconception>>();
+            let expected: &[Misconception] = match part {
+                0 => &[SignError, MissingConstant, WrongTime],
+                _ => &[DegreeRadian, MissingConstant, WrongTime],
+            };
+            for misconception in expected {
+                assert!(offered.contains(misconception), "{} with seed {seed} is missing its {misconception:?} distractor in part {part}", template.id);
+            }
+        }
+    }
+}
+
+#[test]
+fn templates_are_reproducible_from_a_seed() {
+    for template in registry::templates() {
+        for seed in SNAPSHOT_SEEDS {
+            let first = serde_json::to_string(&generate(template, seed)).unwrap();
+            let second = serde_json::to_string(&generate(template, seed)).unwrap();
+            assert_eq!(first, second, "{} isn't reproducible with seed {seed}", template.id);
+        }
+    }
+}
+
+#[test]
+fn templates_match_snapshots() {
+    let update = ::std::env::var("UPDATE_SNAPSHOTS").is_ok();
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("snapshots");
+    for template in registry::templates() {
+        for seed in SNAPSHOT_SEEDS {
+            let rendered = serde_json::to_string_pretty(&generate(template, seed)).unwrap() + "\n";
+            let path = directory.join(format!("{}.{seed}.json", template.id));
+            if update {
+                fs::write(&path, rendered).unwrap();
+                continue;
+            }
+            let expected = fs::read_to_string(&path).unwrap_or_else(|_| panic!("Missing snapshot {}, run with UPDATE_SNAPSHOTS=1", path.display()));
+            assert_eq!(rendered, expected, "{} with seed {seed} no longer matches {}", template.id, path.display());
+        }
+    }
+}
@@ -31,6 +31,7 @@ pub mod Credits {
     use diesel::prelude::*;
     use diesel::dsl::{min, sum};
     use diesel_async::{
+        AsyncPgConnection,
         RunQueryDsl,
         scoped_futures::ScopedFutureExt
     };
@@ -38,7 +39,7 @@ pub mod Credits {
     use crate::{
         State::AppState,
         DB::UserCreditsQueryResult,
-        Schema::allocatedcredits,
+        Schema::{allocatedcredits, generation, hooked_sql_types::GenerationStatus},
         Routes::verify::db::InsertableAllocatedCredits,
     };
 
@@ -302,6 +303,47 @@ pub mod Credits {
         return Ok((total_credits - amount as i64, DateTime::from_timestamp(next_expire_at.unwrap_or(1), 0).unwrap().naive_utc()));
     }
 
+    // Gives back what a failed generation was charged, refundedat is cleared whenever the job is charged
+    // again so each charge is refunded at most once
+    // Run it in the same transaction that fails the job, returns the user and how many credits they got back
+    pub async fn refund_failed_generation(conn: &mut AsyncPgConnection, job_id: uuid::Uuid, duration: i64) -> Result<Option<(i64, i32)>, diesel::result::Error> {
+        let utc = Utc::now().naive_utc();
+        let refunded = diesel::update(generation::table.filter(
+                            generation::jobid.eq(job_id)
+                                .and(generation::status.eq(GenerationStatus::Failed))
+                                .and(generation::refundedat.is_null())
+                        ))
+                        .set(generation::refundedat.eq(utc))
+                        .returning((generation::userid, generation::creditsused))
+                        .get_result::<(i64, i16)>(conn)
+                        .await
+                        .optional()?;
+        let Some((user_id, credits)) = refunded else {
+            return Ok(None);
+        };
+        if credits > 0 {
+            let _ = diesel::insert_into(allocatedcredits::table)
+                        .values(&InsertableAllocatedCredits {
+                            credits: credits as i32,
+                            userid: user_id,
+                            expireat: utc + TimeDelta::new(duration, 0).unwrap(),
+                        })
+                        .execute(conn)
+                        .await?;
+        }
+        Ok(Some((user_id, credits as i32)))
+    }
+
+    // The next balance lookup goes back to Postgres and picks up whatever changed
+    pub async fn clear_cached_credits(redis_conn: &mut RedisConnection, user_id: i64) -> Result<(), deadpool_redis::redis::RedisError> {
+        let credits_key = format!("user:{user_id}:cred:t");
+        let expire_key = format!("user:{user_id}:cred:e");
+        pipe()
+            .cmd("DEL").arg(&[&credits_key]).ignore()
+            .cmd("DEL").arg(&[&expire_key]).ignore()
+            .query_async::<_, ()>(redis_conn).await
+    }
+
     pub async fn get_total_credits_with_conn(user_id: i64, mut redis_conn: RedisConnection, postgres_conn: PostgresConnection) -> Result<(i64, NaiveDateTime, RedisConnection, PostgresConnection), FetchError> {
         let utc = Utc::now().naive_utc();
         let utc_now = utc.and_utc().timestamp();
@@ -483,7 +525,7 @@ pub mod Auth {
 #[allow(non_snake_case)]
 pub mod DB {
     use diesel::prelude::*;
-    use crate::db_schema::{hooked_sql_types::{SupportTicketState, SupportWhoAreYou}, supporttickets, supportticketmessages};
+    use crate::db_schema::{hooked_sql_types::{SupportTicketState, SupportWhoAreYou}, generationdeadletters, supporttickets, supportticketmessages};
     use chrono::naive::NaiveDateTime;
 
     #[derive(Queryable, Debug)]
@@ -527,12 +569,22 @@ pub mod DB {
         pub createdat: NaiveDateTime, 
         pub lastchanged: NaiveDateTime, 
     }
+
+    #[derive(Insertable)]
+    #[diesel(table_name = generationdeadletters)]
+    pub struct InsertableDeadLetter<'a> {
+        pub jobid: uuid::Uuid,
+        pub userid: i64,
+        pub body: &'a str,
+        pub reason: &'a str,
+        pub attempts: i32,
+        pub createdat: NaiveDateTime,
+    }
 }
 
 #[allow(non_snake_case)]
 pub mod Email {
-    use ::std::sync::Arc;
-    use crate::State::{self, AppState}; 
+    use crate::State::AppState;
     use deadpool_redis::redis::cmd;
     use base64::prelude::*;
     use trust_dns_resolver::TokioAsyncResolver;
@@ -541,16 +593,16 @@ pub mod Email {
     use super::common_types::SESEmailBlock::EmailBlock;
     use sha2::{Sha256, Digest};
     use diesel::prelude::*;
-    use diesel_async::RunQueryDsl;
+    use diesel_async::{pooled_connection::deadpool::Pool as PostgresPool, AsyncPgConnection, RunQueryDsl};
 
-    pub async fn is_safe_to_send_to(appstate: Arc<State::InternalAppState>, email: &str) -> bool {
+    pub async fn is_safe_to_send_to(postgres: &PostgresPool<AsyncPgConnection>, email: &str) -> bool {
         let email_identifier;
         {
             let mut hasher = Sha256::new();
             hasher.update(format!("{}rapidl-nonce!#?", email));
             email_identifier = hex::encode(hasher.finalize());
         }
-        let Ok(mut conn) = appstate.postgres.get().await else {
+        let Ok(mut conn) = postgres.get().await else {
             return false;
         };
         let result = problematicemails::table.filter(problematicemails::hash.eq(&email_identifier))
@@ -651,6 +703,363 @@ pub mod Email {
     }
 }
 
+#[allow(non_snake_case)]
+pub mod Storage {
+    use ::std::{path::PathBuf, sync::Arc};
+    use axum::async_trait;
+    use aws_sdk_s3::operation::get_object::GetObjectError;
+    use crate::Constants;
+
+    #[derive(Debug)]
+    pub enum PaperStoreError {
+        NotFound,
+        InvalidState,
+        Backend(String),
+    }
+    impl ::std::fmt::Display for PaperStoreError {
+        fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+            match self {
+                PaperStoreError::NotFound => write!(f, "Paper not found"),
+                PaperStoreError::InvalidState => write!(f, "Paper is in an invalid state"),
+                PaperStoreError::Backend(err) => write!(f, "Paper store failure, {err}"),
+            }
+        }
+    }
+
+    // Where the gzipped blob of each successful generation lives
+    #[async_trait]
+    pub trait PaperStore: Send + Sync {
+        async fn put(&self, job_id: uuid::Uuid, blob: Vec<u8>) -> Result<(), PaperStoreError>;
+        async fn get(&self, job_id: uuid::Uuid) -> Result<Vec<u8>, PaperStoreError>;
+        // Deleting a paper that was never stored still succeeds
+        async fn delete(&self, job_id: uuid::Uuid) -> Result<(), PaperStoreError>;
+    }
+
+    fn key(job_id: uuid::Uuid) -> String {
+        format!("{job_id}.rapidl.gz")
+    }
+
+    pub struct S3PaperStore {
+        client: aws_sdk_s3::Client,
+        bucket: String,
+    }
+    impl S3PaperStore {
+        pub fn new(client: aws_sdk_s3::Client, bucket: String) -> Self {
+            Self { client, bucket }
+        }
+    }
+    #[async_trait]
+    impl PaperStore for S3PaperStore {
+        async fn put(&self, job_id: uuid::Uuid, blob: Vec<u8>) -> Result<(), PaperStoreError> {
+            self.client.put_object()
+                .body(aws_sdk_s3::primitives::ByteStream::from(blob))
+                .bucket(&self.bucket)
+                .key(key(job_id))
+                .content_encoding("gzip")
+                .send()
+                .await
+                .map_err(|err| PaperStoreError::Backend(err.into_service_error().to_string()))?;
+            Ok(())
+        }
+        async fn get(&self, job_id: uuid::Uuid) -> Result<Vec<u8>, PaperStoreError> {
+            let object = self.client.get_object()
+                            .bucket(&self.bucket)
+                            .key(key(job_id))
+                            .send()
+                            .await
+                            .map_err(|err| match err.into_service_error() {
+                                GetObjectError::NoSuchKey(_) => PaperStoreError::NotFound,
+                                GetObjectError::InvalidObjectState(_) => PaperStoreError::InvalidState,
+                                err => PaperStoreError::Backend(err.to_string()),
+                            })?;
+            let bytes = object.body.collect().await.map_err(|err| PaperStoreError::Backend(err.to_string()))?;
+            Ok(bytes.into_bytes().to_vec())
+        }
+        async fn delete(&self, job_id: uuid::Uuid) -> Result<(), PaperStoreError> {
+            self.client.delete_object()
+                .bucket(&self.bucket)
+                .key(key(job_id))
+                .send()
+                .await
+                .map_err(|err| PaperStoreError::Backend(err.into_service_error().to_string()))?;
+            Ok(())
+        }
+    }
+
+    // Keeps papers in a directory so everything can run on a dev machine without AWS
+    pub struct LocalPaperStore {
+        root: PathBuf,
+    }
+    impl LocalPaperStore {
+        pub fn new(root: PathBuf) -> Self {
+            Self { root }
+        }
+    }
+    #[async_trait]
+    impl PaperStore for LocalPaperStore {
+        async fn put(&self, job_id: uuid::Uuid, blob: Vec<u8>) -> Result<(), PaperStoreError> {
+            tokio::fs::create_dir_all(&self.root).await.map_err(|err| PaperStoreError::Backend(err.to_string()))?;
+            // Written aside and renamed so a reader never sees half a paper
+            let partial = self.root.join(format!("{}.partial", key(job_id)));
+            tokio::fs::write(&partial, blob).await.map_err(|err| PaperStoreError::Backend(err.to_string()))?;
+            tokio::fs::rename(&partial, self.root.join(key(job_id))).await.map_err(|err| PaperStoreError::Backend(err.to_string()))
+        }
+        async fn get(&self, job_id: uuid::Uuid) -> Result<Vec<u8>, PaperStoreError> {
+            tokio::fs::read(self.root.join(key(job_id))).await.map_err(|err| match err.kind() {
+                ::std::io::ErrorKind::NotFound => PaperStoreError::NotFound,
+                _ => PaperStoreError::Backend(err.to_string()),
+            })
+        }
+        async fn delete(&self, job_id: uuid::Uuid) -> Result<(), PaperStoreError> {
+            match tokio::fs::remove_file(self.root.join(key(job_id))).await {
+                Err(err) if err.kind() != ::std::io::ErrorKind::NotFound => Err(PaperStoreError::Backend(err.to_string())),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    // PAPER_STORE picks the backend, "s3" (the default) or "local"
+    pub fn from_config(config: &aws_config::SdkConfig) -> Arc<dyn PaperStore> {
+        match Constants::PAPER_STORE.as_str() {
+            "local" => {
+                tracing::info!("Storing papers in {}", Constants::PAPER_STORE_DIR.display());
+                Arc::new(LocalPaperStore::new(Constants::PAPER_STORE_DIR.clone()))
+            },
+            _ => Arc::new(S3PaperStore::new(aws_sdk_s3::Client::new(config), Constants::GENERATED_BUCKET_NAME.clone())),
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+pub mod Queue {
+    use ::std::sync::Arc;
+    use axum::async_trait;
+    use aws_sdk_sqs::types::MessageSystemAttributeName;
+    use chrono::{TimeDelta, Utc};
+    use diesel::prelude::*;
+    use diesel_async::{
+        pooled_connection::deadpool::Pool as PostgresPool,
+        scoped_futures::ScopedFutureExt,
+        AsyncPgConnection,
+        RunQueryDsl,
+    };
+    use crate::{Constants, Schema::generationqueue};
+
+    #[derive(Debug)]
+    pub struct JobQueueError(pub String);
+    impl ::std::fmt::Display for JobQueueError {
+        fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+            write!(f, "Job queue failure, {}", self.0)
+        }
+    }
+
+    pub struct ReceivedJob {
+        pub body: String,
+        // How many times the job has been handed out, including this one
+        pub receive_count: i32,
+        receipt: String,
+    }
+
+    // Where the serialized SQSBody of each generation waits for the generator
+    #[async_trait]
+    pub trait JobQueue: Send + Sync {
+        async fn send(&self, body: String) -> Result<(), JobQueueError>;
+        // Received jobs are hidden until acked, or handed out again once the visibility timeout passes
+        async fn receive(&self, max_jobs: usize) -> Result<Vec<ReceivedJob>, JobQueueError>;
+        async fn ack(&self, job: &ReceivedJob) -> Result<(), JobQueueError>;
+    }
+
+    pub struct SqsJobQueue {
+        client: aws_sdk_sqs::Client,
+        queue_url: String,
+    }
+    impl SqsJobQueue {
+        pub fn new(client: aws_sdk_sqs::Client, queue_url: String) -> Self {
+            Self { client, queue_url }
+        }
+    }
+    #[async_trait]
+    impl JobQueue for SqsJobQueue {
+        async fn send(&self, body: String) -> Result<(), JobQueueError> {
+            self.client.send_message()
+                .queue_url(&self.queue_url)
+                .message_body(body)
+                .send()
+                .await
+                .map_err(|err| JobQueueError(err.into_service_error().to_string()))?;
+            Ok(())
+        }
+        async fn receive(&self, max_jobs: usize) -> Result<Vec<ReceivedJob>, JobQueueError> {
+            let output = self.client.receive_message()
+                            .queue_url(&self.queue_url)
+                            .max_number_of_messages(max_jobs.clamp(1, 10) as i32)
+                            .wait_time_seconds(20)
+                            .message_system_attribute_names(MessageSystemAttributeName::ApproximateReceiveCount)
+                            .send()
+                            .await
+                            .map_err(|err| JobQueueError(err.into_service_error().to_string()))?;
+            Ok(output.messages.unwrap_or_default().into_iter().filter_map(|message| {
+                let receive_count = message.attributes.as_ref()
+                                        .and_then(|attributes| attributes.get(&MessageSystemAttributeName::ApproximateReceiveCount))
+                                        .and_then(|count| count.parse().ok())
+                                        .unwrap_or(1);
+                Some(ReceivedJob {
+                    body: message.body?,
+                    receive_count,
+                    receipt: message.receipt_handle?,
+                })
+            }).collect())
+        }
+        async fn ack(&self, job: &ReceivedJob) -> Result<(), JobQueueError> {
+            self.client.delete_message()
+                .queue_url(&self.queue_url)
+                .receipt_handle(&job.receipt)
+                .send()
+                .await
+                .map_err(|err| JobQueueError(err.into_service_error().to_string()))?;
+            Ok(())
+        }
+    }
+
+    // Keeps jobs in the generationqueue table so everything can run on a dev machine without AWS
+    pub struct PostgresJobQueue {
+        postgres: PostgresPool<AsyncPgConnection>,
+        visibility_timeout: TimeDelta,
+    }
+    impl PostgresJobQueue {
+        pub fn new(postgres: PostgresPool<AsyncPgConnection>, visibility_timeout_secs: i64) -> Self {
+            Self {
+                postgres,
+                visibility_timeout: TimeDelta::new(visibility_timeout_secs, 0).unwrap(),
+            }
+        }
+    }
+    #[async_trait]
+    impl JobQueue for PostgresJobQueue {
+        async fn send(&self, body: String) -> Result<(), JobQueueError> {
+            let mut conn = self.postgres.get().await.map_err(|err| JobQueueError(err.to_string()))?;
+            let now = Utc::now().naive_utc();
+            diesel::insert_into(generationqueue::table)
+                .values((
+                    generationqueue::body.eq(body),
+                    generationqueue::receivecount.eq(0),
+                    generationqueue::visibleat.eq(now),
+                    generationqueue::createdat.eq(now),
+                ))
+                .execute(&mut conn)
+                .await
+                .map_err(|err| JobQueueError(err.to_string()))?;
+            Ok(())
+        }
+        async fn receive(&self, max_jobs: usize) -> Result<Vec<ReceivedJob>, JobQueueError> {
+            let mut conn = self.postgres.get().await.map_err(|err| JobQueueError(err.to_string()))?;
+            let visibility_timeout = self.visibility_timeout;
+            let received = conn.build_transaction()
+                                .read_write()
+                                .run::<Vec<(i64, String, i32)>, diesel::result::Error, _>(|conn| async move {
+                                    let now = Utc::now().naive_utc();
+                                    // Skipping locked rows lets several workers poll at once
+                                    let ids: Vec<i64> = generationqueue::table.filter(generationqueue::visibleat.le(now))
+                                                            .order(generationqueue::id.asc())
+                                                            .limit(max_jobs as i64)
+                                                            .select(generationqueue::id)
+                                                            .for_update()
+                                                            .skip_locked()
+                                                            .load(conn)
+                                                            .await?;
+                                    if ids.is_empty() {
+                                        return Ok(Vec::new());
+                                    }
+                                    diesel::update(generationqueue::table.filter(generationqueue::id.eq_any(ids)))
+                                        .set((
+                                            generationqueue::receivecount.eq(generationqueue::receivecount + 1),
+                                            generationqueue::visibleat.eq(now + visibility_timeout),
+                                        ))
+                                        .returning((generationqueue::id, generationqueue::body, generationqueue::receivecount))
+                                        .get_results(conn)
+                                        .await
+                                }.scope_boxed())
+                                .await
+                                .map_err(|err| JobQueueError(err.to_string()))?;
+            // The receive count is part of the receipt so a stale ack can't remove a job that was handed out again
+            Ok(received.into_iter().map(|(id, body, receive_count)| ReceivedJob {
+                body,
+                receive_count,
+                receipt: format!("{id}:{receive_count}"),
+            }).collect())
+        }
+        async fn ack(&self, job: &ReceivedJob) -> Result<(), JobQueueError> {
+            let Some((Ok(id), Ok(receive_count))) = job.receipt.split_once(':')
+                                                        .map(|(id, count)| (id.parse::<i64>(), count.parse::<i32>())) else {
+                return Err(JobQueueError(format!("Malformed receipt {}", job.receipt)));
+            };
+            let mut conn = self.postgres.get().await.map_err(|err| JobQueueError(err.to_string()))?;
+            diesel::delete(generationqueue::table.filter(
+                    generationqueue::id.eq(id).and(generationqueue::receivecount.eq(receive_count))
+                ))
+                .execute(&mut conn)
+                .await
+                .map_err(|err| JobQueueError(err.to_string()))?;
+            Ok(())
+        }
+    }
+
+    // JOB_QUEUE picks the backend, "sqs" (the default) or "postgres"
+    pub fn from_config(config: &aws_config::SdkConfig, postgres: PostgresPool<AsyncPgConnection>) -> Arc<dyn JobQueue> {
+        match Constants::JOB_QUEUE.as_str() {
+            "postgres" => {
+                tracing::info!("Queueing generation jobs in Postgres");
+                Arc::new(PostgresJobQueue::new(postgres, *Constants::JOB_QUEUE_VISIBILITY_TIMEOUT_SECS))
+            },
+            _ => Arc::new(SqsJobQueue::new(aws_sdk_sqs::Client::new(config), Constants::GENERATE_QUEUE_URL.clone())),
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+pub mod Progress {
+    use deadpool_redis::{
+        redis::{pipe, RedisResult},
+        Connection as RedisConnection,
+    };
+    use serde::{Deserialize, Serialize};
+    use crate::Schema::hooked_sql_types::GenerationStatus;
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct ProgressEvent {
+        pub status: GenerationStatus,
+        // How far through generating the paper is, out of 100
+        pub progress: u8,
+    }
+    impl ProgressEvent {
+        pub fn new(status: GenerationStatus, progress: u8) -> Self {
+            Self { status, progress }
+        }
+        // Nothing follows these unless the user retries
+        pub fn is_finished(&self) -> bool {
+            matches!(self.status, GenerationStatus::Success | GenerationStatus::Failed | GenerationStatus::Deleting)
+        }
+    }
+
+    pub fn channel(job_id: uuid::Uuid) -> String {
+        format!("gen:job:{job_id}:events")
+    }
+
+    // The last event published, lets a poll that starts part way through pick up the progress
+    pub fn latest_key(job_id: uuid::Uuid) -> String {
+        format!("gen:job:{job_id}:progress")
+    }
+
+    pub async fn publish(redis_conn: &mut RedisConnection, job_id: uuid::Uuid, event: &ProgressEvent) -> RedisResult<()> {
+        let payload = serde_json::to_string(event).expect("Failed to serialize progress event");
+        pipe()
+            .cmd("SET").arg(&[&latest_key(job_id), &payload, "EX", "1800"]).ignore()
+            .cmd("PUBLISH").arg(&[&channel(job_id), &payload]).ignore()
+            .query_async::<_, ()>(redis_conn)
+            .await
+    }
+}
+
 #[allow(non_snake_case)]
 pub mod MinimalState {
     use ::std::sync::Arc;
@@ -743,10 +1152,12 @@ pub mod State {
     pub struct InternalAppState {
         pub postgres: PostgresPool<AsyncPgConnection>,
         pub redis: RedisPool,
+        // Pooled connections can't subscribe, status long polls open their own from this
+        pub redis_pubsub: redis::Client,
         pub http_client: Client,
         pub lambda_client: aws_sdk_lambda::Client,
-        pub sqs_client: aws_sdk_sqs::Client,
-        pub s3_client: aws_sdk_s3::Client,
+        pub job_queue: Arc<dyn crate::Queue::JobQueue>,
+        pub paper_store: Arc<dyn crate::Storage::PaperStore>,
         pub dns_resolver: TokioAsyncResolver,
     }
     pub type AppState = Arc<InternalAppState>;
@@ -772,14 +1183,15 @@ pub mod State {
                 password: Some(REDIS_SESSION_DATABASE_PASS.clone()),
             }
         };
+        let redis_pubsub = redis::Client::open(redis::ConnectionInfo::from(redisconnectioninfo.clone()))?;
         let redisconfig = RedisConfig::from_connection_info(redisconnectioninfo);
         let redispool = redisconfig.create_pool(Some(RedisRuntime::Tokio1)).unwrap();
 
         /* Create AWS clients */
         let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
         let lambda_client = aws_sdk_lambda::Client::new(&config);
-        let sqs_client = aws_sdk_sqs::Client::new(&config);
-        let s3_client = aws_sdk_s3::Client::new(&config);
+        let job_queue = crate::Queue::from_config(&config, pool.clone());
+        let paper_store = crate::Storage::from_config(&config);
 
         /* Create DNS reoslver */
         let resolver = TokioAsyncResolver::tokio(ResolverConfig::cloudflare_tls(), ResolverOpts::default());
@@ -789,10 +1201,11 @@ pub mod State {
         Ok(Arc::new(InternalAppState {
             postgres: pool,
             redis: redispool,
+            redis_pubsub,
             http_client: reqwest::Client::new(),
             lambda_client,
-            sqs_client,
-            s3_client,
+            job_queue,
+            paper_store,
             dns_resolver: resolver,
         }))
     }
@@ -1009,6 +1422,75 @@ pub mod Constants {
         pub static ref GENERATED_BUCKET_NAME: String = {
             dotenvy::var("GENERATED_BUCKET_NAME").expect("No environment variable for GENERATED_BUCKET_NAME").to_owned()
         };
+        pub static ref PAPER_STORE: String = {
+            dotenvy::var("PAPER_STORE").unwrap_or_else(|_| "s3".to_owned())
+        };
+        pub static ref PAPER_STORE_DIR: ::std::path::PathBuf = {
+            dotenvy::var("PAPER_STORE_DIR").unwrap_or_else(|_| "papers".to_owned()).into()
+        };
+        pub static ref JOB_QUEUE: String = {
+            dotenvy::var("JOB_QUEUE").unwrap_or_else(|_| "sqs".to_owned())
+        };
+        pub static ref JOB_QUEUE_VISIBILITY_TIMEOUT_SECS: i64 = {
+            let maybe = dotenvy::var("JOB_QUEUE_VISIBILITY_TIMEOUT_SECS");
+            let mut time = 900;
+            if let Ok(secs) = maybe {
+                match secs.parse() {
+                    Ok(new_secs) if new_secs > 0 => {
+                        time = new_secs;
+                        tracing::info!("Using custom JOB_QUEUE_VISIBILITY_TIMEOUT_SECS: {time}");
+                    },
+                    _ => tracing::info!("Failed to parse JOB_QUEUE_VISIBILITY_TIMEOUT_SECS, using default, {time}"),
+                }
+            }
+            time
+        };
+        // How long a POST /generate with an Idempotency-Key hands back the same job instead of starting another
+        pub static ref IDEMPOTENCY_WINDOW_SECS: i64 = {
+            let maybe = dotenvy::var("IDEMPOTENCY_WINDOW_SECS");
+            let mut time = 86400;
+            if let Ok(secs) = maybe {
+                match secs.parse() {
+                    Ok(new_secs) if new_secs > 0 => {
+                        time = new_secs;
+                        tracing::info!("Using custom IDEMPOTENCY_WINDOW_SECS: {time}");
+                    },
+                    _ => tracing::info!("Failed to parse IDEMPOTENCY_WINDOW_SECS, using default, {time}"),
+                }
+            }
+            time
+        };
+        // How long a claimed Idempotency-Key stays "pending" in Redis. A request that dies mid-way frees the key after this
+        // rather than locking it out for the whole window, one that is only slow is still caught by the unique key in Postgres
+        pub static ref IDEMPOTENCY_PENDING_SECS: i64 = {
+            let maybe = dotenvy::var("IDEMPOTENCY_PENDING_SECS");
+            let mut time = 30;
+            if let Ok(secs) = maybe {
+                match secs.parse() {
+                    Ok(new_secs) if new_secs > 0 => {
+                        time = new_secs;
+                        tracing::info!("Using custom IDEMPOTENCY_PENDING_SECS: {time}");
+                    },
+                    _ => tracing::info!("Failed to parse IDEMPOTENCY_PENDING_SECS, using default, {time}"),
+                }
+            }
+            time
+        };
+        // How long a status long poll waits for a change, has to answer before API Gateway gives up on the request
+        pub static ref STATUS_POLL_MAX_SECS: u64 = {
+            let maybe = dotenvy::var("STATUS_POLL_MAX_SECS");
+            let mut time = 25;
+            if let Ok(secs) = maybe {
+                match secs.parse() {
+                    Ok(new_secs) if new_secs > 0 => {
+                        time = new_secs;
+                        tracing::info!("Using custom STATUS_POLL_MAX_SECS: {time}");
+                    },
+                    _ => tracing::info!("Failed to parse STATUS_POLL_MAX_SECS, using default, {time}"),
+                }
+            }
+            time
+        };
         pub static ref COMPLAINT_BOUNCE_NEXT_RESET: i64 = {
             let maybe = dotenvy::var("COMPLAINT_BOUNCE_NEXT_RESET");
             let mut time = 604800;
@@ -27,7 +27,7 @@ pub async fn middleware(req: Request<Body>, next: Next<Body>) -> Result<Response
     parts.headers.append(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_str(&*Constants::ORIGIN_URL).unwrap());
     parts.headers.append(ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_str("GET,PUT,POST,OPTIONS,DELETE").unwrap());
     parts.headers.append(ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_str("true").unwrap());
-    parts.headers.append(ACCESS_CONTROL_ALLOW_HEADERS, HeaderValue::from_str("content-type,withcredentials,recaptcha").unwrap());
+    parts.headers.append(ACCESS_CONTROL_ALLOW_HEADERS, HeaderValue::from_str("content-type,withcredentials,recaptcha,idempotency-key").unwrap());
     parts.headers.append(ACCESS_CONTROL_EXPOSE_HEADERS, HeaderValue::from_str("x-atk-ex,X-Atk-Ex,x-set-credits,X-Set-Credits").unwrap());
     return Ok(Response::from_parts(parts, body))
 }
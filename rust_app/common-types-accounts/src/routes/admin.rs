@@ -1 +1,2 @@
 pub mod support;
+pub mod generate;
@@ -0,0 +1,203 @@
+use axum::{
+    extract::{
+        Extension,
+        State,
+        Query,
+    },
+    http::StatusCode,
+    Json
+};
+use chrono::{NaiveDateTime, Utc};
+use serde::Serialize;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::RunQueryDsl;
+use deadpool_redis::redis::cmd;
+
+use crate::{
+    Schema::{generation, generationdeadletters, hooked_sql_types::GenerationStatus},
+    Response::{ServerResponse, internal_server_error, status_response},
+    State::AppState,
+    Middleware::validate_access_auth::AccessTokenDescription,
+    Progress::{self, ProgressEvent},
+};
+
+mod db;
+use db::{Pagination, RequeueRequest, DeadLetter};
+
+const PAGE_SIZE: i64 = 10;
+
+#[derive(Serialize)]
+pub struct DeadLetterPayload {
+    id: i64,
+    #[serde(rename = "jobId")]
+    job_id: uuid::Uuid,
+    #[serde(rename = "userId")]
+    user_id: i64,
+    // The queue record exactly as the generator last received it
+    body: String,
+    reason: String,
+    attempts: i32,
+    #[serde(rename = "createdAt")]
+    created_at: NaiveDateTime,
+    #[serde(rename = "requeuedAt")]
+    requeued_at: Option<NaiveDateTime>,
+    #[serde(rename = "requeuedBy")]
+    requeued_by: Option<i64>,
+}
+
+impl From<DeadLetter> for DeadLetterPayload {
+    fn from(dead_letter: DeadLetter) -> Self {
+        Self {
+            id: dead_letter.id,
+            job_id: dead_letter.jobid,
+            user_id: dead_letter.userid,
+            body: dead_letter.body,
+            reason: dead_letter.reason,
+            attempts: dead_letter.attempts,
+            created_at: dead_letter.createdat,
+            requeued_at: dead_letter.requeuedat,
+            requeued_by: dead_letter.requeuedby,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct GroupPayload {
+    content: Vec<DeadLetterPayload>,
+    total_pages: Option<usize>,
+}
+
+// GET API endpoint
+#[tracing::instrument(skip(access_token, appstate, pagination), fields(UserId=%access_token.user_id,request="GET /admin/generate/dead-letters",page=%pagination.page))]
+pub async fn get_request(Extension(access_token): Extension<AccessTokenDescription>, State(appstate): State<AppState>, Query(pagination): Query<Pagination>) -> Result<Json<GroupPayload>, ServerResponse> {
+    if !access_token.has_support_privilege {
+        return Err(status_response(StatusCode::UNAUTHORIZED, "Not Authorised"));
+    }
+    if pagination.page == 0 {
+        return Err(status_response(StatusCode::BAD_REQUEST, "Pages start at 1"));
+    }
+
+    let mut conn = appstate.postgres.get().await.map_err(|err| {
+        tracing::error!("Failed to fetch Postgres connection, {err}");
+        internal_server_error("Internal Service Error")
+    })?;
+
+    let mut query = generationdeadletters::table.into_boxed();
+    if !pagination.include_requeued {
+        query = query.filter(generationdeadletters::requeuedat.is_null());
+    }
+    let dead_letters: Vec<DeadLetter> = query.order(generationdeadletters::id.desc())
+                                            .offset((pagination.page as i64 - 1) * PAGE_SIZE)
+                                            .limit(PAGE_SIZE)
+                                            .select(DeadLetter::as_select())
+                                            .load(&mut conn)
+                                            .await.map_err(|err| {
+                                                tracing::error!("Failed to query page {}, with page size, {PAGE_SIZE}, due to {err}", pagination.page);
+                                                internal_server_error("Internal Service Error")
+                                            })?;
+
+    let mut total_pages = None;
+    if pagination.get_total_pages {
+        let mut query = generationdeadletters::table.into_boxed();
+        if !pagination.include_requeued {
+            query = query.filter(generationdeadletters::requeuedat.is_null());
+        }
+        let total = query.count()
+                        .get_result::<i64>(&mut conn)
+                        .await.map_err(|err| {
+                            tracing::error!("Failed to query total page size due to {err}");
+                            internal_server_error("Internal Service Error")
+                        })?;
+        total_pages = Some((total as f64 / PAGE_SIZE as f64).ceil() as usize);
+    }
+
+    Ok(Json(GroupPayload {
+        content: dead_letters.into_iter().map(DeadLetterPayload::from).collect(),
+        total_pages,
+    }))
+}
+
+// POST API endpoint
+// Puts the dead lettered record back on the queue with a fresh set of attempts
+#[tracing::instrument(skip(access_token, appstate, request), fields(UserId=%access_token.user_id,request="POST /admin/generate/dead-letters/requeue",id=%request.id))]
+pub async fn post_requeue_request(Extension(access_token): Extension<AccessTokenDescription>, State(appstate): State<AppState>, Query(request): Query<RequeueRequest>) -> Result<(), ServerResponse> {
+    if !access_token.has_support_privilege {
+        return Err(status_response(StatusCode::UNAUTHORIZED, "Not Authorised"));
+    }
+
+    let mut conn = appstate.postgres.get().await.map_err(|err| {
+        tracing::error!("Failed to fetch Postgres connection, {err}");
+        internal_server_error("Internal Service Error")
+    })?;
+    let appstate = appstate.clone();
+    conn.build_transaction()
+        .repeatable_read()
+        .run::<Result<(), ServerResponse>, diesel::result::Error, _>(|conn| async move {
+            let Some(dead_letter) = generationdeadletters::table.find(request.id)
+                                        .select(DeadLetter::as_select())
+                                        .for_update()
+                                        .first(conn)
+                                        .await
+                                        .optional()? else {
+                return Ok(Err(status_response(StatusCode::NOT_FOUND, "No such dead letter")));
+            };
+            if dead_letter.requeuedat.is_some() {
+                return Ok(Err(status_response(StatusCode::CONFLICT, "Dead letter has already been requeued")));
+            }
+
+            // The user may have retried or deleted the generation since
+            let status: Option<GenerationStatus> = generation::table.filter(generation::jobid.eq(dead_letter.jobid))
+                                                        .select(generation::status)
+                                                        .for_update()
+                                                        .first(conn)
+                                                        .await
+                                                        .optional()?;
+            match status {
+                Some(GenerationStatus::Failed) => (),
+                Some(_) => return Ok(Err(status_response(StatusCode::CONFLICT, "Only failed generations can be requeued"))),
+                None => return Ok(Err(status_response(StatusCode::CONFLICT, "Generation no longer exists"))),
+            }
+
+            // refundedat is left alone, a requeue isn't charged so there is nothing left to refund
+            diesel::update(generation::table.filter(generation::jobid.eq(dead_letter.jobid)))
+                .set((
+                    generation::status.eq(GenerationStatus::Waiting),
+                    generation::lastchanged.eq(Utc::now().naive_utc()),
+                    generation::attempts.eq(0),
+                    generation::failurereason.eq(None::<String>),
+                ))
+                .execute(conn)
+                .await?;
+            diesel::update(generationdeadletters::table.find(dead_letter.id))
+                .set((
+                    generationdeadletters::requeuedat.eq(Utc::now().naive_utc()),
+                    generationdeadletters::requeuedby.eq(access_token.user_id),
+                ))
+                .execute(conn)
+                .await?;
+
+            if let Err(queue_err) = appstate.job_queue.send(dead_letter.body).await {
+                tracing::error!("Failed to requeue dead letter {} due to {queue_err}", dead_letter.id);
+                return Err(diesel::result::Error::RollbackTransaction);
+            }
+
+            // redis cache dont really matter, the row says Waiting until a worker claims it
+            if let Ok(mut redis_conn) = appstate.redis.get().await {
+                let generate_redis_key = format!("gen:job:{}", dead_letter.jobid);
+                let _ = cmd("SET")
+                    .arg(&[&generate_redis_key, "Waiting", "EX", "1800"])
+                    .query_async::<_, ()>(&mut redis_conn)
+                    .await;
+                if let Err(err) = Progress::publish(&mut redis_conn, dead_letter.jobid, &ProgressEvent::new(GenerationStatus::Waiting, 0)).await {
+                    tracing::warn!("Failed to publish progress for {}, {:?}", dead_letter.jobid, err);
+                }
+            }
+
+            Ok(Ok(()))
+        }.scope_boxed())
+        .await.map_err(|err| {
+            tracing::error!("Transaction error: {err}");
+            internal_server_error("Internal Service Error")
+        })?
+}
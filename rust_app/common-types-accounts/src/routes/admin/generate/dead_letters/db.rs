@@ -0,0 +1,32 @@
+use serde::Deserialize;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use crate::Schema::generationdeadletters;
+
+#[derive(Deserialize)]
+pub struct Pagination {
+    pub page: usize,
+    pub get_total_pages: bool,
+    // Dead letters that were already requeued are hidden unless asked for
+    #[serde(default)]
+    pub include_requeued: bool,
+}
+
+#[derive(Deserialize)]
+pub struct RequeueRequest {
+    pub id: i64,
+}
+
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = generationdeadletters)]
+pub struct DeadLetter {
+    pub id: i64,
+    pub jobid: uuid::Uuid,
+    pub userid: i64,
+    pub body: String,
+    pub reason: String,
+    pub attempts: i32,
+    pub createdat: NaiveDateTime,
+    pub requeuedat: Option<NaiveDateTime>,
+    pub requeuedby: Option<i64>,
+}
@@ -1,4 +1,4 @@
-use ::std::collections::HashSet;
+use ::std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 use axum::{
     extract::{
@@ -6,9 +6,12 @@ use axum::{
         State,
     },
     http::{StatusCode, HeaderMap, HeaderName, HeaderValue, header::CONTENT_TYPE},
-    response::IntoResponse,
     Json
 };
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind;
+use diesel_async::scoped_futures::ScopedFutureExt;
 use diesel_async::RunQueryDsl;
 use serde_json::to_string;
 use deadpool_redis::redis::cmd;
@@ -17,15 +20,15 @@ use garde::Validate;
 use crate::{
     Response::{ServerResponse, internal_server_error, status_response},
     State::AppState, 
-    Credits::{get_total_credits, decrement_total_credits, increment_total_credits},
+    Credits::{get_total_credits, decrement_total_credits, increment_total_credits, IncrementTotalCreditsError},
     Middleware::validate_access_auth::AccessTokenDescription,
-    common_types::Generate::{SQSBody, GenerateOption},
+    common_types::Generate::{SQSBody, GenerateOption, PaperComposition, PaperOrdering, PERSONALISED_CATEGORY},
     Schema::{generation, hooked_sql_types::GenerationStatus},
     Constants,
 };
 mod db;
 
-use db::{RequestPayload, InsertableGeneration};
+use db::{GenerateRequest, RequestPayload, PersonalisedPayload, InsertableGeneration};
 
 fn generate_options_to_string(ids: &[GenerateOption]) -> String {
     let mut result = String::new();
@@ -38,11 +41,8 @@ fn generate_options_to_string(ids: &[GenerateOption]) -> String {
     result
 }
 
-// POST API endpoint
-#[tracing::instrument(skip(access_token, appstate, user_request), fields(UserId=%access_token.user_id,request="/generate"))]
-pub async fn request(Extension(access_token): Extension<AccessTokenDescription>, State(appstate): State<AppState>, Json(user_request): Json<RequestPayload>) -> Result<impl IntoResponse, ServerResponse> {
-    // Payload validation
-    let validation_result = user_request.validate(&user_request);
+fn validate_chosen(user_request: &RequestPayload) -> Result<(), ServerResponse> {
+    let validation_result = user_request.validate(user_request);
     if let Err(err) = validation_result {
         tracing::info!("Validation failed with reason: {err}");
         return Err(status_response(StatusCode::BAD_REQUEST, err));
@@ -65,9 +65,201 @@ pub async fn request(Extension(access_token): Extension<AccessTokenDescription>,
     if user_request.choices.len() > i16::MAX as usize {
         return Err(status_response(StatusCode::BAD_REQUEST, "Too many choices"));
     }
-    
-    let required_credits = user_request.choices.len() as i32;
-    let user_id = access_token.user_id;
+    Ok(())
+}
+
+fn credit_headers(total_credits: i64, next_expire_at: NaiveDateTime) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, "text/plain".parse().unwrap());
+    headers.insert(HeaderName::from_static("x-set-credits"), HeaderValue::from_str(total_credits.to_string().as_ref()).unwrap());
+    headers.insert(HeaderName::from_static("x-next-fetch"), HeaderValue::from_str(next_expire_at.and_utc().timestamp().to_string().as_ref()).unwrap());
+    headers
+}
+
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<String>, ServerResponse> {
+    let Some(value) = headers.get("idempotency-key") else {
+        return Ok(None);
+    };
+    match value.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= 255 => Ok(Some(key.to_owned())),
+        _ => Err(status_response(StatusCode::BAD_REQUEST, "Idempotency-Key must be between 1 and 255 visible ASCII characters")),
+    }
+}
+
+// A replay gets the job that was started the first time round and the user's credits as they are now
+async fn replay(appstate: &AppState, user_id: i64, job_id: String) -> Result<(HeaderMap, String), ServerResponse> {
+    tracing::info!("Replaying generation {job_id} for a repeated Idempotency-Key");
+    let (credits, next_expire_at) = get_total_credits(appstate, user_id).await.map_err(|err| {
+        tracing::error!("Failed to obtain total credits, {:?}", err);
+        internal_server_error("Failed to query")
+    })?;
+    Ok((credit_headers(credits, next_expire_at), job_id))
+}
+
+// A key can only be replayed for the request it was first used with, anything else is a client bug. Keys used before
+// request hashes were kept have nothing to compare against and replay as they always did
+fn same_request(stored_hash: Option<&str>, request_hash: &str) -> Result<(), ServerResponse> {
+    match stored_hash {
+        Some(stored_hash) if stored_hash != request_hash => Err(status_response(StatusCode::UNPROCESSABLE_ENTITY, "Idempotency-Key was already used with a different request")),
+        _ => Ok(()),
+    }
+}
+
+async fn find_idempotent_job(appstate: &AppState, user_id: i64, key: &str) -> Result<Option<(Uuid, Option<String>)>, ServerResponse> {
+    let mut postgres_conn = appstate.postgres.get().await.map_err(|err| {
+        tracing::error!("Failed to fetch Postgres connection, {err}");
+        internal_server_error("Internal Service Error")
+    })?;
+    let since = Utc::now().naive_utc() - TimeDelta::new(*Constants::IDEMPOTENCY_WINDOW_SECS, 0).unwrap();
+    generation::table.filter(
+            generation::userid.eq(user_id)
+                .and(generation::idempotencykey.eq(key))
+                .and(generation::createdat.gt(since))
+        )
+        .select((generation::jobid, generation::idempotencyhash))
+        .first(&mut postgres_conn)
+        .await
+        .optional()
+        .map_err(|err| {
+            tracing::error!("Failed to query for idempotency key, {err}");
+            internal_server_error("Internal Service Error")
+        })
+}
+
+// POST API endpoint
+// Requests sharing an Idempotency-Key within IDEMPOTENCY_WINDOW_SECS get back the first request's job, so a client
+// retrying after a timeout isn't charged twice. Reusing the key for a different request is rejected
+#[tracing::instrument(skip(access_token, appstate, headers, user_request), fields(UserId=%access_token.user_id,request="/generate"))]
+pub async fn request(Extension(access_token): Extension<AccessTokenDescription>, State(appstate): State<AppState>, headers: HeaderMap, Json(user_request): Json<RequestPayload>) -> Result<(HeaderMap, String), ServerResponse> {
+    idempotent_create(appstate, access_token.user_id, &headers, GenerateRequest::Chosen(user_request)).await
+}
+
+// POST API endpoint (personalised)
+// The generator picks the topics from the user's marked answers, so there's no GenerateId to choose and the row is
+// flagged as personalised instead
+#[tracing::instrument(skip(access_token, appstate, headers, user_request), fields(UserId=%access_token.user_id,request="/generate/personalised"))]
+pub async fn personalised_request(Extension(access_token): Extension<AccessTokenDescription>, State(appstate): State<AppState>, headers: HeaderMap, Json(user_request): Json<PersonalisedPayload>) -> Result<(HeaderMap, String), ServerResponse> {
+    idempotent_create(appstate, access_token.user_id, &headers, GenerateRequest::Personalised(user_request)).await
+}
+
+async fn idempotent_create(appstate: AppState, user_id: i64, headers: &HeaderMap, user_request: GenerateRequest) -> Result<(HeaderMap, String), ServerResponse> {
+    let Some(key) = idempotency_key(headers)? else {
+        return create(appstate, user_id, user_request, None).await;
+    };
+    let request_hash = user_request.request_hash();
+
+    let mut redis_conn = appstate.redis.get().await.map_err(|err| {
+        tracing::error!("Failed to fetch Redis connection, {err}");
+        internal_server_error("Internal Service Error")
+    })?;
+    // Holds the key while the first request is still creating its job, saves going to Postgres for the common case of a
+    // quick retry but it's the row that decides
+    let idempotency_redis_key = format!("gen:idem:{user_id}:{key}");
+    let pending = Constants::IDEMPOTENCY_PENDING_SECS.to_string();
+    let claimed = cmd("SET")
+                    .arg(&[&idempotency_redis_key, "pending", "NX", "EX", &pending])
+                    .query_async::<_, Option<String>>(&mut redis_conn)
+                    .await
+                    .map_err(|err| {
+                        tracing::error!("Redis SET command failed, {:?}", err);
+                        internal_server_error("Internal Service Error")
+                    })?
+                    .is_some();
+    if !claimed {
+        let existing = cmd("GET")
+                        .arg(&[&idempotency_redis_key])
+                        .query_async::<_, Option<String>>(&mut redis_conn)
+                        .await
+                        .map_err(|err| {
+                            tracing::error!("Redis GET command failed, {:?}", err);
+                            internal_server_error("Internal Service Error")
+                        })?;
+        return match existing {
+            Some(recorded) if recorded != "pending" => {
+                // Recorded as "{job id}:{request hash}", older keys only hold the job id
+                let (job_id, stored_hash) = match recorded.split_once(':') {
+                    Some((job_id, stored_hash)) => (job_id, Some(stored_hash)),
+                    None => (recorded.as_str(), None),
+                };
+                same_request(stored_hash, &request_hash)?;
+                replay(&appstate, user_id, job_id.to_owned()).await
+            },
+            _ => Err(status_response(StatusCode::CONFLICT, "A request with this Idempotency-Key is still in progress")),
+        };
+    }
+
+    // Redis only holds the key for the window at best, the generation row is what records it was used
+    let result = match find_idempotent_job(&appstate, user_id, &key).await {
+        Ok(Some((job_id, stored_hash))) => match same_request(stored_hash.as_deref(), &request_hash) {
+            Ok(()) => replay(&appstate, user_id, job_id.to_string()).await,
+            Err(err) => Err(err),
+        },
+        Ok(None) => create(appstate.clone(), user_id, user_request, Some((key, request_hash.clone()))).await,
+        Err(err) => Err(err),
+    };
+    // Only now does the key hold on for the full window
+    let window = Constants::IDEMPOTENCY_WINDOW_SECS.to_string();
+    let outcome = match &result {
+        Ok((_, job_id)) => cmd("SET").arg(&[&idempotency_redis_key, &format!("{job_id}:{request_hash}"), "EX", &window]).query_async::<_, ()>(&mut redis_conn).await,
+        // Nothing was charged, the client is free to try again with the same key
+        Err(_) => cmd("DEL").arg(&[&idempotency_redis_key]).query_async::<_, ()>(&mut redis_conn).await,
+    };
+    if let Err(err) = outcome {
+        tracing::error!("Failed to record outcome for Idempotency-Key, {:?}", err);
+    }
+    result
+}
+
+async fn delete_generation(appstate: &AppState, job_id: Uuid) -> Result<(), String> {
+    let mut postgres_conn = appstate.postgres.get().await.map_err(|err| err.to_string())?;
+    diesel::delete(generation::table.filter(generation::jobid.eq(job_id)))
+        .execute(&mut postgres_conn)
+        .await
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+// Undoes a generation that was inserted but never made it onto the queue. The row goes first, otherwise
+// the reaper would pick it up as stuck and queue or refund it again. If it can't be deleted the credits
+// are left for the reaper to sort out
+async fn rollback_generation(appstate: AppState, user_id: i64, job_id: Uuid, required_credits: i32) {
+    if let Err(err) = delete_generation(&appstate, job_id).await {
+        tracing::error!("Failed to delete generation {job_id} for rollback, leaving it to the reaper, {err}");
+        return;
+    }
+    let rollback_result = increment_total_credits(appstate, user_id, required_credits, *Constants::STANDARD_CREDITS_EXPIRE_AFTER_SECS, None, None).await;
+    if let Err(rollback_err) = rollback_result {
+        tracing::error!("Rollback total credits failed for {user_id}, error: {rollback_err}");
+    }
+}
+
+// idempotency is the Idempotency-Key and the hash of the request it was used with
+async fn create(appstate: AppState, user_id: i64, user_request: GenerateRequest, idempotency: Option<(String, String)>) -> Result<(HeaderMap, String), ServerResponse> {
+    let (payload_id, choices, composition, locale, notify_by_email) = match user_request {
+        GenerateRequest::Chosen(user_request) => {
+            validate_chosen(&user_request)?;
+            (Some(user_request.payload_id), user_request.choices, user_request.composition, user_request.locale, user_request.notify_by_email)
+        },
+        GenerateRequest::Personalised(user_request) => {
+            if let Err(err) = user_request.validate(&()) {
+                tracing::info!("Validation failed with reason: {err}");
+                return Err(status_response(StatusCode::BAD_REQUEST, err));
+            }
+            // The generator picks the topics once it has looked at the user's marked answers
+            let composition = PaperComposition {
+                length: user_request.length,
+                weights: HashMap::new(),
+                ordering: PaperOrdering::Interleaved,
+            };
+            (None, Vec::new(), Some(composition), user_request.locale, user_request.notify_by_email)
+        },
+    };
+
+    // Papers without a composition keep the old price of a credit per topic
+    let required_credits = match composition {
+        Some(ref composition) => composition.required_credits(),
+        None => choices.len() as i32,
+    };
     let (credits, _) = get_total_credits(&appstate, user_id).await.map_err(|err| {
         tracing::error!("Failed to obtain total credits, {:?}", err);
         internal_server_error("Failed to query")
@@ -75,57 +267,96 @@ pub async fn request(Extension(access_token): Extension<AccessTokenDescription>,
     if required_credits as i64 > credits {
         return Err(status_response(StatusCode::BAD_REQUEST, "Insuffecient credits"));
     }
-    let (next_total_credits, next_expire_at) = decrement_total_credits(appstate.clone(), user_id, required_credits, None, None).await.map_err(|err| {
-        tracing::error!("Decrement total credits failed: {err}");
-        internal_server_error("Unknown Error")
-    })?;
 
     let generate_uuid = Uuid::new_v4();
     let generate_id = generate_uuid.to_string();
+    let (idempotency_key, idempotency_hash) = idempotency.clone().unzip();
     let created_at = chrono::Utc::now().naive_utc();
+    // The row goes in before any credits are taken, (userid, idempotencykey) is unique so of two requests racing
+    // with the same key only one gets this far
     {
-        let mut postgres_conn = match appstate.postgres.get().await {
-            Ok(postgres_conn) => postgres_conn,
-            Err(err) => {
-                tracing::error!("Failed to open postgres connection, {err}");
-                let rollback_result = increment_total_credits(appstate, user_id, required_credits, *Constants::STANDARD_CREDITS_EXPIRE_AFTER_SECS, None, None).await;
-                if let Err(rollback_err) = rollback_result {
-                    tracing::error!("Rollback total credits failed for {user_id}, error: {rollback_err}");
-                }
+        let mut postgres_conn = appstate.postgres.get().await.map_err(|err| {
+            tracing::error!("Failed to open postgres connection, {err}");
+            internal_server_error("Internal Service Error")
+        })?;
+
+        let insertable = InsertableGeneration {
+            userid: user_id,
+            status: GenerationStatus::Waiting,
+            createdat: created_at,
+            jobid: generate_uuid,
+            creditsused: required_credits as i16,
+            displayname: String::new(),
+            category: payload_id.as_ref().map_or_else(|| PERSONALISED_CATEGORY.to_owned(), ToString::to_string),
+            options: generate_options_to_string(&choices),
+            composition: composition.as_ref().map(|composition| to_string(composition).expect("Failed to serialize composition")),
+            locale: locale.to_string(),
+            lastchanged: created_at,
+            notifybyemail: notify_by_email,
+            idempotencykey: idempotency_key,
+            idempotencyhash: idempotency_hash,
+            personalised: payload_id.is_none(),
+        };
+        let insert_result = postgres_conn.build_transaction()
+                            .read_write()
+                            .run::<(), diesel::result::Error, _>(|conn| async move {
+                                // A key is only held for IDEMPOTENCY_WINDOW_SECS, after that the old generation gives it up
+                                if let Some(ref key) = insertable.idempotencykey {
+                                    let since = created_at - TimeDelta::new(*Constants::IDEMPOTENCY_WINDOW_SECS, 0).unwrap();
+                                    diesel::update(generation::table.filter(
+                                            generation::userid.eq(user_id)
+                                                .and(generation::idempotencykey.eq(key))
+                                                .and(generation::createdat.le(since))
+                                        ))
+                                        .set(generation::idempotencykey.eq(None::<String>))
+                                        .execute(conn)
+                                        .await?;
+                                }
+                                diesel::insert_into(generation::table)
+                                    .values(&insertable)
+                                    .execute(conn)
+                                    .await?;
+                                Ok(())
+                            }.scope_boxed())
+                            .await;
+
+        match (insert_result, idempotency) {
+            (Ok(()), _) => (),
+            // Another request with the same key got its row in first
+            (Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)), Some((key, request_hash))) => {
+                return match find_idempotent_job(&appstate, user_id, &key).await? {
+                    Some((job_id, stored_hash)) => {
+                        same_request(stored_hash.as_deref(), &request_hash)?;
+                        replay(&appstate, user_id, job_id.to_string()).await
+                    },
+                    None => Err(status_response(StatusCode::CONFLICT, "A request with this Idempotency-Key is still in progress")),
+                };
+            },
+            (Err(err), _) => {
+                tracing::error!("Insert postgres failure: {}", err);
                 return Err(internal_server_error("Internal Service Error"));
             },
-        };
+        }
+    }
 
-        let insert_result = diesel::insert_into(generation::table)
-                            .values(&InsertableGeneration {
-                                userid: user_id,
-                                status: GenerationStatus::Waiting,
-                                createdat: created_at,
-                                jobid: generate_uuid,
-                                creditsused: required_credits as i16,
-                                displayname: String::new(),
-                                category: user_request.payload_id.to_string(),
-                                options: generate_options_to_string(&user_request.choices),
-                            })
-                            .execute(&mut postgres_conn)
-                            .await;
-        
-        if let Err(err) = insert_result {
-            tracing::error!("Insert postgres failure: {}", err);
-            let rollback_result = increment_total_credits(appstate, user_id, required_credits, *Constants::STANDARD_CREDITS_EXPIRE_AFTER_SECS, None, None).await;
-            if let Err(rollback_err) = rollback_result {
-                tracing::error!("Rollback total credits failed for {user_id}, error: {rollback_err}");
+    let (next_total_credits, next_expire_at) = match decrement_total_credits(appstate.clone(), user_id, required_credits, None, None).await {
+        Ok(credits) => credits,
+        Err(err) => {
+            tracing::error!("Decrement total credits failed: {err}");
+            // Nothing was taken, only the row has to go
+            if let Err(err) = delete_generation(&appstate, generate_uuid).await {
+                tracing::error!("Failed to delete generation {generate_uuid} that was never charged for, {err}");
             }
-            return Err(internal_server_error("Internal Service Error"));
-        }
-    }   
+            return Err(match err {
+                IncrementTotalCreditsError::NotEnoughCredits => status_response(StatusCode::BAD_REQUEST, "Insuffecient credits"),
+                _ => internal_server_error("Unknown Error"),
+            });
+        },
+    };
     let mut redis_conn = match appstate.redis.get().await {
         Ok(redis_conn) => redis_conn,
         Err(err) => {
-            let rollback_result = increment_total_credits(appstate, user_id, required_credits, *Constants::STANDARD_CREDITS_EXPIRE_AFTER_SECS, None, None).await;
-            if let Err(rollback_err) = rollback_result {
-                tracing::error!("Rollback total credits failed for {user_id}, error: {rollback_err}");
-            }
+            rollback_generation(appstate, user_id, generate_uuid, required_credits).await;
             tracing::error!("Failed to fetch Redis connection: {}", err);
             return Err(internal_server_error("Internal Service Error"));
         }
@@ -136,10 +367,7 @@ pub async fn request(Extension(access_token): Extension<AccessTokenDescription>,
         .query_async::<_, ()>(&mut redis_conn)
         .await
     {
-        let rollback_result = increment_total_credits(appstate, user_id, required_credits, *Constants::STANDARD_CREDITS_EXPIRE_AFTER_SECS, None, None).await;
-        if let Err(rollback_err) = rollback_result {
-            tracing::error!("Rollback total credits failed for {user_id}, error: {rollback_err}");
-        }
+        rollback_generation(appstate, user_id, generate_uuid, required_credits).await;
         tracing::error!("Redis set command failed, {:?}", err);
         return Err(internal_server_error("Internal Service Error"))
     }
@@ -147,20 +375,17 @@ pub async fn request(Extension(access_token): Extension<AccessTokenDescription>,
         user_id,
         created_at,
         job_id: generate_id.clone(),
-        gen_id: user_request.payload_id,
-        opts: user_request.choices,
+        personalised: payload_id.is_none(),
+        gen_id: payload_id,
+        opts: choices,
+        composition,
+        locale,
     };
-    let sqs_result = appstate.sqs_client
-                        .send_message()
-                        .queue_url(&*Constants::GENERATE_QUEUE_URL)
-                        .message_body(to_string(&generate_payload).expect("Failed to serialize generate info"))
-                        .send()
+    let queue_result = appstate.job_queue
+                        .send(to_string(&generate_payload).expect("Failed to serialize generate info"))
                         .await;
-    if let Err(sqs_err) = sqs_result {
-        let rollback_result = increment_total_credits(appstate, user_id, required_credits, *Constants::STANDARD_CREDITS_EXPIRE_AFTER_SECS, None, None).await;
-        if let Err(rollback_err) = rollback_result {
-            tracing::error!("Rollback total credits failed for {user_id}, error: {rollback_err}");
-        }
+    if let Err(queue_err) = queue_result {
+        rollback_generation(appstate, user_id, generate_uuid, required_credits).await;
         if let Err(err) = cmd("DEL")
             .arg(&[&generate_redis_key])
             .query_async::<_, ()>(&mut redis_conn)
@@ -168,15 +393,10 @@ pub async fn request(Extension(access_token): Extension<AccessTokenDescription>,
         {
             tracing::error!("Redis DEL command failed for rollback, {:?}", err);
         }
-        tracing::error!("Failed to add generate task to queue due to {}", sqs_err.into_service_error());
+        tracing::error!("Failed to add generate task to queue due to {queue_err}");
         return Err(internal_server_error("Failed to add task to queue"));
     }
     
-    let mut headers = HeaderMap::new();
-    headers.insert(CONTENT_TYPE, "text/plain".parse().unwrap());
-    headers.insert(HeaderName::from_static("x-set-credits"), HeaderValue::from_str(next_total_credits.to_string().as_ref()).unwrap());
-    headers.insert(HeaderName::from_static("x-next-fetch"), HeaderValue::from_str(next_expire_at.and_utc().timestamp().to_string().as_ref()).unwrap());
-
-    Ok((headers, generate_id))
+    Ok((credit_headers(next_total_credits, next_expire_at), generate_id))
 }
 
@@ -2,9 +2,52 @@ use diesel::prelude::*;
 use chrono::NaiveDateTime;
 use serde::Deserialize;
 use garde::Validate;
-use crate::common_types::Generate::{GenerateId, GenerateOption::{self, *}};
+use sha2::{Sha256, Digest};
+use crate::common_types::Generate::{GenerateId, Locale, PaperComposition, GenerateOption::{self, *}};
+
 use crate::Schema::{generation, hooked_sql_types::GenerationStatus};
 
+// Either the user picks the topics (POST /generate), or leaves it to the generator to pick from their
+// marked answers (POST /generate/personalised)
+pub enum GenerateRequest {
+    Chosen(RequestPayload),
+    Personalised(PersonalisedPayload),
+}
+
+impl GenerateRequest {
+    // Stored against an Idempotency-Key so a replay can tell it's for the same request. Built by hand rather than
+    // serialised so the weights map hashes the same whatever order it iterates in
+    pub fn request_hash(&self) -> String {
+        let canonical = match self {
+            GenerateRequest::Chosen(payload) => {
+                let choices = payload.choices.iter().map(ToString::to_string).collect::<Vec<String>>().join(",");
+                let composition = payload.composition.as_ref().map(|composition| {
+                    let mut weights = composition.weights.iter().map(|(option, weight)| format!("{option}={weight}")).collect::<Vec<String>>();
+                    weights.sort();
+                    format!("{}/{}/{:?}", composition.length, weights.join(","), composition.ordering)
+                }).unwrap_or_default();
+                format!("chosen|{}|{choices}|{composition}|{}|{}", payload.payload_id, payload.locale, payload.notify_by_email)
+            },
+            GenerateRequest::Personalised(payload) => format!("personalised|{}|{}|{}", payload.length, payload.locale, payload.notify_by_email),
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(canonical);
+        hex::encode(hasher.finalize())
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct PersonalisedPayload {
+    #[garde(custom(check_paper_length))]
+    pub length: usize,
+    #[serde(default)]
+    #[garde(skip)]
+    pub locale: Locale,
+    #[serde(default)]
+    #[garde(skip)]
+    pub notify_by_email: bool,
+}
+
 #[derive(Deserialize, Validate)]
 #[garde(context(RequestPayload))]
 pub struct RequestPayload {
@@ -12,6 +55,39 @@ pub struct RequestPayload {
     pub choices: Vec<GenerateOption>,
     #[garde(skip)]
     pub payload_id: GenerateId,
+    #[serde(default)]
+    #[garde(custom(check_composition))]
+    pub composition: Option<PaperComposition>,
+    #[serde(default)]
+    #[garde(skip)]
+    pub locale: Locale,
+    // Emails the user once the paper is ready
+    #[serde(default)]
+    #[garde(skip)]
+    pub notify_by_email: bool,
+}
+
+fn check_composition(value: &Option<PaperComposition>, context: &RequestPayload) -> garde::Result {
+    let Some(composition) = value else {
+        return Ok(());
+    };
+    check_paper_length(&composition.length, &())?;
+    for (generate_option, weight) in composition.weights.iter() {
+        if !context.choices.contains(generate_option) {
+            return Err(garde::Error::new("weight given for an option that wasn't chosen"));
+        }
+        if *weight == 0 || *weight > 10 {
+            return Err(garde::Error::new("weights must be between 1 and 10"));
+        }
+    }
+    Ok(())
+}
+
+fn check_paper_length(value: &usize, _context: &()) -> garde::Result {
+    match value {
+        5 | 10 | 20 => Ok(()),
+        _ => Err(garde::Error::new("length can only be 5, 10 or 20")),
+    }
 }
 
 fn check_generate_options(value: &Vec<GenerateOption>, context: &RequestPayload) -> garde::Result {
@@ -43,8 +119,6 @@ fn check_generate_options(value: &Vec<GenerateOption>, context: &RequestPayload)
             }
             Ok(())
         },
-
-        _ => Err(garde::Error::new("bad data")),
     }
 }
 
@@ -60,4 +134,11 @@ pub struct InsertableGeneration {
     pub category: String,
     pub options: String,
     pub displayname: String,
+    pub composition: Option<String>,
+    pub locale: String,
+    pub lastchanged: NaiveDateTime,
+    pub notifybyemail: bool,
+    pub idempotencykey: Option<String>,
+    pub idempotencyhash: Option<String>,
+    pub personalised: bool,
 }
@@ -1,4 +1,4 @@
-use ::std::collections::HashSet;
+use ::std::{collections::HashSet, io::{Read, Write}, time::Duration};
 use axum::{
     extract::{
         Extension,
@@ -8,22 +8,26 @@ use axum::{
     http::StatusCode,
     Json
 };
+use futures_util::StreamExt;
 use chrono::NaiveDateTime;
 use serde::Serialize;
 use diesel::prelude::*;
 use diesel_async::scoped_futures::ScopedFutureExt;
 use diesel_async::RunQueryDsl;
-use aws_sdk_s3::operation::get_object::GetObjectError;
 use deadpool_redis::redis::cmd;
 use garde::Validate;
 use base64::prelude::*;
+use common_types::Generate::GenerateOption;
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
 
 use crate::{
-    Schema::{generation, hooked_sql_types::GenerationStatus},
+    Schema::{generation, markedanswers, hooked_sql_types::GenerationStatus},
     Response::{ServerResponse, internal_server_error, status_response},
     State::AppState, 
     Middleware::validate_access_auth::AccessTokenDescription,
-    common_types::Generate::{SQSBody, str_to_generation_options, str_to_generation_id},
+    Credits::{decrement_total_credits, increment_total_credits, IncrementTotalCreditsError},
+    Storage::PaperStoreError,
+    Progress::{self, ProgressEvent},
     Constants,
 };
 
@@ -56,8 +60,8 @@ pub struct GenerationNoContent {
     jobid: uuid::Uuid,
 }
 
-mod db;
-use db::{GenerationNameChangeQuery, GenerationQuery, GenerationBatchQuery, GenerationSelectable, GenerationSelectableWithJobId};
+pub mod db;
+use db::{GenerationContentQuery, GenerationEventsQuery, GenerationNameChangeQuery, GenerationQuery, GenerationBatchQuery, GenerationSelectable, GenerationSelectableWithJobId, MarkedAnswersPayload, InsertableMarkedAnswer};
 
 // POST API endpoint (retry)
 #[tracing::instrument(skip(access_token, appstate, query), fields(UserId=%access_token.user_id,request="/generated/content[post]",id=%query.id))]
@@ -75,7 +79,7 @@ pub async fn post_retry_request(Extension(access_token): Extension<AccessTokenDe
             internal_server_error("Internal Service Error")
         })?;
         let appstate = appstate.clone();
-        let _ = conn.build_transaction()
+        conn.build_transaction()
                         .repeatable_read()
                         .run::<Result<&'static str, ServerResponse>, diesel::result::Error, _>(|conn| async move {
                             let generation_details = generation::table.filter(generation::userid.eq(access_token.user_id).and(generation::jobid.eq(uuid_job_id)))
@@ -88,37 +92,65 @@ pub async fn post_retry_request(Extension(access_token): Extension<AccessTokenDe
                                 _ => return Ok(Err(status_response( StatusCode::CONFLICT, "You cannot retry a generation that has not failed" ))),
                             }
 
-                            let (Ok(gen_id), Ok(gen_opts)) = (str_to_generation_id(&generation_details.category), str_to_generation_options(&generation_details.options)) else {
-                                tracing::error!("Generation {uuid_job_id} for {} has bad category/options, failed to serialize", access_token.user_id);
+                            let Some(generate_payload) = generation_details.to_sqs_body(access_token.user_id, uuid_job_id) else {
+                                tracing::error!("Generation {uuid_job_id} for {} has bad category/options/composition/locale, failed to serialize", access_token.user_id);
                                 return Ok(Err(internal_server_error("Bad record data")));
                             };
 
+                            // The failure was refunded, so the retry is paid for again
+                            let charge = match generation_details.refundedat {
+                                Some(_) => generation_details.creditsused as i32,
+                                None => 0,
+                            };
+                            if charge > 0 {
+                                match decrement_total_credits(appstate.clone(), access_token.user_id, charge, None, None).await {
+                                    Ok(_) => (),
+                                    Err(IncrementTotalCreditsError::NotEnoughCredits) => return Ok(Err(status_response(StatusCode::BAD_REQUEST, "Insuffecient credits"))),
+                                    Err(err) => {
+                                        tracing::error!("Decrement total credits failed: {err}");
+                                        return Ok(Err(internal_server_error("Unknown Error")));
+                                    },
+                                }
+                            }
+                            let refund_charge = |appstate: AppState| async move {
+                                if charge > 0 {
+                                    if let Err(err) = increment_total_credits(appstate, access_token.user_id, charge, *Constants::STANDARD_CREDITS_EXPIRE_AFTER_SECS, None, None).await {
+                                        tracing::error!("Rollback total credits failed for {}, error: {err}", access_token.user_id);
+                                    }
+                                }
+                            };
+
                             let updated_rows = diesel::update(generation::table.filter(generation::userid.eq(access_token.user_id).and(generation::jobid.eq(uuid_job_id))))
-                                                        .set(generation::status.eq(GenerationStatus::Waiting))
+                                                        .set((
+                                                            generation::status.eq(GenerationStatus::Waiting),
+                                                            generation::lastchanged.eq(chrono::Utc::now().naive_utc()),
+                                                            // A retry gets a fresh set of attempts
+                                                            generation::attempts.eq(0),
+                                                            generation::failurereason.eq(None::<String>),
+                                                            generation::refundedat.eq(None::<NaiveDateTime>),
+                                                        ))
                                                         .execute(conn)
-                                                        .await?;
-                            if updated_rows == 0 {
-                                return Ok(Err(internal_server_error("Updated 0 rows")));
+                                                        .await;
+                            match updated_rows {
+                                Ok(0) => {
+                                    refund_charge(appstate.clone()).await;
+                                    return Ok(Err(internal_server_error("Updated 0 rows")));
+                                },
+                                Ok(_) => (),
+                                Err(err) => {
+                                    refund_charge(appstate.clone()).await;
+                                    return Err(err);
+                                },
                             }
 
-                            let generate_payload = SQSBody {
-                                gen_id,
-                                user_id: access_token.user_id,
-                                created_at: generation_details.createdat,
-                                job_id: uuid_job_id.to_string(),
-                                opts: gen_opts,
+                            let Ok(message_body) = serde_json::to_string(&generate_payload) else {
+                                tracing::error!("Failed to serialize SQSBody for generation retry");
+                                refund_charge(appstate.clone()).await;
+                                return Err(diesel::result::Error::RollbackTransaction);
                             };
-                            let sqs_result = appstate.sqs_client
-                                                .send_message()
-                                                .queue_url(&*Constants::GENERATE_QUEUE_URL)
-                                                .message_body(serde_json::to_string(&generate_payload).map_err(|x| {
-                                                    tracing::error!("Failed to serialize SQSBody for generation retry: {x}"); 
-                                                    diesel::result::Error::RollbackTransaction
-                                                })?)
-                                                .send()
-                                                .await;
-                            if let Err(sqs_err) = sqs_result {
-                                tracing::error!("Failed to add retry generate task to queue due to {}", sqs_err.into_service_error());
+                            if let Err(queue_err) = appstate.job_queue.send(message_body).await {
+                                tracing::error!("Failed to add retry generate task to queue due to {queue_err}");
+                                refund_charge(appstate.clone()).await;
                                 return Err(diesel::result::Error::RollbackTransaction);
                             }
 
@@ -136,7 +168,7 @@ pub async fn post_retry_request(Extension(access_token): Extension<AccessTokenDe
                         .await.map_err(|err| {
                             tracing::error!("Transaction error: {err}");
                             internal_server_error("Internal Service Error")
-                        })?;
+                        })??;
     }
 
     Ok(())
@@ -172,6 +204,84 @@ pub async fn post_request(Extension(access_token): Extension<AccessTokenDescript
     Ok(())
 }
 
+// POST API endpoint (marked answers)
+// Records how the user did on each question of a finished paper, personalised papers are picked from these.
+// The template registry lives in aws-lambda-generate so the lambda hands over topic_of to look topics up in it
+#[tracing::instrument(skip(access_token, appstate, payload, topic_of), fields(UserId=%access_token.user_id,request="/generated/content/answers[post]",id=%payload.id))]
+pub async fn post_answers_request(Extension(access_token): Extension<AccessTokenDescription>, State(appstate): State<AppState>, Json(payload): Json<MarkedAnswersPayload>, topic_of: fn(&str) -> Option<GenerateOption>) -> Result<(), ServerResponse> {
+    let validation_result = payload.validate(&());
+    if let Err(err) = validation_result {
+        tracing::info!("Validation failed with reason: {err}");
+        return Err(status_response(StatusCode::BAD_REQUEST, err));
+    }
+    if payload.answers.iter().any(|answer| answer.awarded > answer.available) {
+        return Err(status_response(StatusCode::BAD_REQUEST, "Cannot award more marks than are available"));
+    }
+
+    let uuid_job_id = uuid::Uuid::try_parse(&payload.id).map_err(|_| status_response(StatusCode::BAD_REQUEST, "Invalid ID"))?;
+    let answered_at = chrono::Utc::now().naive_utc();
+    let user_id = access_token.user_id;
+
+    let mut conn = appstate.postgres.get().await.map_err(|err| {
+        tracing::error!("Failed to fetch Postgres connection, {err}");
+        internal_server_error("Internal Service Error")
+    })?;
+    conn.build_transaction()
+        .read_committed()
+        .run::<Result<(), ServerResponse>, diesel::result::Error, _>(|conn| async move {
+            let (status, fingerprints) = match generation::table.filter(generation::userid.eq(user_id).and(generation::jobid.eq(uuid_job_id)))
+                                                        .select((generation::status, generation::fingerprints))
+                                                        .first::<(GenerationStatus, Option<String>)>(conn)
+                                                        .await {
+                                                            Ok(data) => data,
+                                                            Err(diesel::result::Error::NotFound) => return Ok(Err(status_response(StatusCode::NOT_FOUND, "Content not found"))),
+                                                            Err(err) => return Err(err),
+                                                        };
+            if status != GenerationStatus::Success {
+                return Ok(Err(status_response(StatusCode::CONFLICT, "You can only mark a finished generation")));
+            }
+            // Only answers to questions that were actually on this paper count
+            let fingerprints = fingerprints.unwrap_or_default();
+            let on_paper: HashSet<&str> = fingerprints.split(',').collect();
+            let mut records = Vec::with_capacity(payload.answers.len());
+            for answer in payload.answers.iter() {
+                let Some((template_id, _)) = answer.fingerprint.split_once(':') else {
+                    return Ok(Err(status_response(StatusCode::BAD_REQUEST, "Invalid fingerprint")));
+                };
+                if !on_paper.contains(answer.fingerprint.as_str()) {
+                    return Ok(Err(status_response(StatusCode::BAD_REQUEST, "Answer given for a question not on this paper")));
+                }
+                let Some(topic) = topic_of(template_id) else {
+                    return Ok(Err(status_response(StatusCode::BAD_REQUEST, "Unknown template")));
+                };
+                records.push(InsertableMarkedAnswer {
+                    userid: user_id,
+                    jobid: uuid_job_id,
+                    templateid: template_id.to_string(),
+                    topic: topic.to_string(),
+                    marksawarded: answer.awarded,
+                    marksavailable: answer.available,
+                    answeredat: answered_at,
+                });
+            }
+
+            // Marking a paper again replaces the previous attempt
+            diesel::delete(markedanswers::table.filter(markedanswers::userid.eq(user_id).and(markedanswers::jobid.eq(uuid_job_id))))
+                .execute(conn)
+                .await?;
+            diesel::insert_into(markedanswers::table)
+                .values(&records)
+                .execute(conn)
+                .await?;
+            Ok(Ok(()))
+        }.scope_boxed())
+        .await
+        .map_err(|err| {
+            tracing::error!("Transaction error: {err}");
+            internal_server_error("Internal Service Error")
+        })?
+}
+
 // GET BATCH API endpoint
 #[tracing::instrument(skip(access_token, appstate, query), fields(UserId=%access_token.user_id,request="/generated/content/batch"))]
 pub async fn get_batch_request(Extension(access_token): Extension<AccessTokenDescription>, State(appstate): State<AppState>, Query(query): Query<GenerationBatchQuery>) -> Result<Json<Vec<GenerationNoContent>>, ServerResponse> {
@@ -240,9 +350,79 @@ pub async fn get_batch_request(Extension(access_token): Extension<AccessTokenDes
     Ok(Json(returned_details))
 }
 
+pub enum StoredGeneration {
+    // Anything other than Success, there is no paper to hand out yet
+    Pending(GenerationStatus),
+    Ready(GenerationSelectable, NaiveDateTime, Vec<u8>),
+}
+
+// Looks up a generation the user owns and fetches its paper from S3 once it has succeeded,
+// shared by every route that hands out the paper itself
+pub async fn load_generation(appstate: &AppState, user_id: i64, uuid_job_id: uuid::Uuid) -> Result<StoredGeneration, ServerResponse> {
+    let generation_details: GenerationSelectable;
+    {
+        let mut conn = appstate.postgres.get().await.map_err(|err| {
+            tracing::error!("Failed to fetch Postgres connection, {err}");
+            internal_server_error("Internal Service Error")
+        })?;
+        generation_details = generation::table.filter(generation::userid.eq(user_id).and(generation::jobid.eq(uuid_job_id)))
+                                                                        .select(GenerationSelectable::as_select())
+                                                                        .first(&mut conn)
+                                                                        .await.map_err(|err| {
+                                                                            tracing::error!("Failed to query for generation details, id {uuid_job_id}, error: {err}");
+                                                                            internal_server_error("Internal Service Error")
+                                                                        })?;
+    }
+
+    match generation_details.status {
+        GenerationStatus::Working | GenerationStatus::Failed | GenerationStatus::Deleting | GenerationStatus::Waiting => return Ok(StoredGeneration::Pending(generation_details.status)),
+        GenerationStatus::Success => (),
+    }
+
+    let Some(finishedon) = generation_details.finishedon else {
+        tracing::error!("Generation status is successful yet there is no finishedon timestamp for {uuid_job_id}");
+        return Err(internal_server_error("Unexpected error"));
+    };
+
+    let bytes = appstate.paper_store.get(uuid_job_id).await.map_err(|err| match err {
+        PaperStoreError::NotFound => {
+            tracing::error!("No paper stored for job {uuid_job_id} but job was marked as success?");
+            internal_server_error("Unexpected error")
+        },
+        PaperStoreError::InvalidState => {
+            tracing::error!("Paper for {uuid_job_id} has an invalid state?");
+            internal_server_error("Object has invalid state")
+        },
+        err => {
+            tracing::error!("Failed to fetch paper for {uuid_job_id}, {err}");
+            internal_server_error("Failed to read object")
+        },
+    })?;
+
+    Ok(StoredGeneration::Ready(generation_details, finishedon, bytes))
+}
+
+// Stored papers start with "RPDL", a u16 schema version and a length prefixed generator version inside the gzip
+// stream (see aws_lambda_generate::generate::paper::blob), this hands back the bare msgpack older clients expect
+pub fn strip_blob_frame(gzipped: &[u8]) -> ::std::io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    GzDecoder::new(gzipped).read_to_end(&mut buf)?;
+    let body = match buf.strip_prefix(b"RPDL".as_slice()) {
+        // Papers from before the frame are already bare
+        None => return Ok(gzipped.to_vec()),
+        Some(rest) => rest.get(3..)
+                        .and_then(|after_version| after_version.get(rest[2] as usize..))
+                        .ok_or_else(|| ::std::io::Error::new(::std::io::ErrorKind::InvalidData, "truncated blob header"))?,
+    };
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(body)?;
+    encoder.finish()
+}
+
 // GET API endpoint
+// The blob is gzipped msgpack of the paper, with `framed=true` it keeps the RPDL header it is stored with
 #[tracing::instrument(skip(access_token, appstate, query), fields(UserId=%access_token.user_id,request="/generated/content[get]",id=%query.id))]
-pub async fn get_request(Extension(access_token): Extension<AccessTokenDescription>, State(appstate): State<AppState>, Query(query): Query<GenerationQuery>) -> Result<Json<GenerationContent>, ServerResponse> {
+pub async fn get_request(Extension(access_token): Extension<AccessTokenDescription>, State(appstate): State<AppState>, Query(query): Query<GenerationContentQuery>) -> Result<Json<GenerationContent>, ServerResponse> {
     let validation_result = query.validate(&());
     if let Err(err) = validation_result {
         tracing::info!("Validation failed with reason: {err}");
@@ -276,62 +456,18 @@ pub async fn get_request(Extension(access_token): Extension<AccessTokenDescripti
         }
     }
 
-    let generation_details: GenerationSelectable;
-    {
-        let mut conn = appstate.postgres.get().await.map_err(|err| {
-            tracing::error!("Failed to fetch Postgres connection, {err}");
-            internal_server_error("Internal Service Error")
-        })?;
-        generation_details = generation::table.filter(generation::userid.eq(access_token.user_id).and(generation::jobid.eq(uuid_job_id)))
-                                                                        .select(GenerationSelectable::as_select())
-                                                                        .first(&mut conn)
-                                                                        .await.map_err(|err| {
-                                                                            tracing::error!("Failed to query for generation details, id {uuid_job_id}, error: {err}");
-                                                                            internal_server_error("Internal Service Error")
-                                                                        })?;
-    }
-
-    match generation_details.status {
-        GenerationStatus::Working | GenerationStatus::Failed | GenerationStatus::Deleting | GenerationStatus::Waiting => return Ok(Json(GenerationContent { status: generation_details.status, content: None })),
-        GenerationStatus::Success => (),
-    }
-
-    let Some(finishedon) = generation_details.finishedon else {
-        tracing::error!("Generation status is successful yet there is no finishedon timestamp for {uuid_job_id}");
-        return Err(internal_server_error("Unexpected error"));
+    let (generation_details, finishedon, bytes) = match load_generation(&appstate, access_token.user_id, uuid_job_id).await? {
+        StoredGeneration::Pending(status) => return Ok(Json(GenerationContent { status, content: None })),
+        StoredGeneration::Ready(generation_details, finishedon, bytes) => (generation_details, finishedon, bytes),
     };
-
-
-    let get_result = appstate.s3_client
-                            .get_object()
-                            .bucket(&*Constants::GENERATED_BUCKET_NAME)
-                            .key(format!("{uuid_job_id}.rapidl.gz"))
-                            .send()
-                            .await;
-
-    let blob = match get_result {
-        Err(sdk_err) => {
-            match sdk_err.into_service_error() {
-                GetObjectError::NoSuchKey(_) => {
-                    tracing::error!("No such key for job {uuid_job_id} but job was marked as success?");
-                    return Err(internal_server_error("Unexpected error"));
-                },
-                GetObjectError::InvalidObjectState(_) => {
-                    tracing::error!("Object {uuid_job_id}.rapidl.gz has an invalid state?");
-                    return Err(internal_server_error("Object has invalid state"));
-                },
-                err @ _ => {
-                    tracing::error!("Handelled service error: {err}");
-                    return Err(internal_server_error("Bad error"));
-                },
-            }
-        },
-        Ok(blob) => blob,
+    let bytes = if query.framed {
+        bytes
+    } else {
+        strip_blob_frame(&bytes).map_err(|err| {
+            tracing::error!("Failed to strip frame from paper {uuid_job_id}, {err}");
+            internal_server_error("Failed to read object")
+        })?
     };
-    let bytes = blob.body.collect().await.map(|data| data.into_bytes()).map_err(|err| {
-                                                                                tracing::error!("Bytestream error: {err}");
-                                                                                internal_server_error("Failed to read object")
-                                                                            })?;
     let data_blob = BASE64_STANDARD.encode(bytes);
 
     Ok(Json(GenerationContent {
@@ -348,6 +484,84 @@ pub async fn get_request(Extension(access_token): Extension<AccessTokenDescripti
     }))
 }
 
+// GET API endpoint (events)
+// Long polls for the status. Answers straight away when it has moved on from the status and progress the client last
+// saw, otherwise waits up to STATUS_POLL_MAX_SECS for the next change. API Gateway buffers whole responses, so every
+// change is its own request rather than an event stream
+#[tracing::instrument(skip(access_token, appstate, query), fields(UserId=%access_token.user_id,request="/generated/content/events[get]",id=%query.id))]
+pub async fn get_events_request(Extension(access_token): Extension<AccessTokenDescription>, State(appstate): State<AppState>, Query(query): Query<GenerationEventsQuery>) -> Result<Json<ProgressEvent>, ServerResponse> {
+    let validation_result = query.validate(&());
+    if let Err(err) = validation_result {
+        tracing::info!("Validation failed with reason: {err}");
+        return Err(status_response(StatusCode::BAD_REQUEST, err));
+    }
+
+    let uuid_job_id = uuid::Uuid::try_parse(&query.id).map_err(|_| status_response(StatusCode::BAD_REQUEST, "Invalid ID"))?;
+
+    // Subscribed before the status is read so nothing published in between is missed
+    let mut pubsub = appstate.redis_pubsub.get_async_connection().await.map_err(|err| {
+        tracing::error!("Failed to open Redis pub/sub connection, {err}");
+        internal_server_error("Internal Service Error")
+    })?.into_pubsub();
+    pubsub.subscribe(Progress::channel(uuid_job_id)).await.map_err(|err| {
+        tracing::error!("Failed to subscribe to progress for {uuid_job_id}, {err}");
+        internal_server_error("Internal Service Error")
+    })?;
+
+    let status: GenerationStatus;
+    {
+        let mut conn = appstate.postgres.get().await.map_err(|err| {
+            tracing::error!("Failed to fetch Postgres connection, {err}");
+            internal_server_error("Internal Service Error")
+        })?;
+        status = generation::table.filter(generation::userid.eq(access_token.user_id).and(generation::jobid.eq(uuid_job_id)))
+                    .select(generation::status)
+                    .first(&mut conn)
+                    .await
+                    .optional()
+                    .map_err(|err| {
+                        tracing::error!("Failed to query for generation status, id {uuid_job_id}, error: {err}");
+                        internal_server_error("Internal Service Error")
+                    })?
+                    .ok_or_else(|| status_response(StatusCode::NOT_FOUND, "Content not found"))?;
+    }
+
+    // The last published event is only trusted while it agrees with Postgres, it can be left over from before a retry
+    let mut latest = None;
+    if let Ok(mut redis_conn) = appstate.redis.get().await {
+        if let Ok(Some(payload)) = cmd("GET").arg(&[&Progress::latest_key(uuid_job_id)]).query_async::<_, Option<String>>(&mut redis_conn).await {
+            latest = serde_json::from_str::<ProgressEvent>(&payload).ok();
+        }
+    }
+    let current = match latest {
+        Some(event) if event.status == status => event,
+        _ => {
+            let progress = if status == GenerationStatus::Success { 100 } else { 0 };
+            ProgressEvent::new(status, progress)
+        },
+    };
+
+    let seen = |event: &ProgressEvent| query.status.as_ref() == Some(&event.status) && query.progress == Some(event.progress);
+    if current.is_finished() || !seen(&current) {
+        return Ok(Json(current));
+    }
+
+    let mut updates = Box::pin(pubsub.into_on_message().filter_map(|message| async move {
+        let payload: String = message.get_payload().ok()?;
+        serde_json::from_str::<ProgressEvent>(&payload).ok()
+    }));
+    let next = tokio::time::timeout(Duration::from_secs(*Constants::STATUS_POLL_MAX_SECS), async {
+        while let Some(event) = updates.next().await {
+            if !seen(&event) {
+                return Some(event);
+            }
+        }
+        None
+    }).await;
+    // Nothing changed in time, the client asks again with the same event
+    Ok(Json(next.ok().flatten().unwrap_or(current)))
+}
+
 // DELETE API endpoint
 #[tracing::instrument(skip(access_token, appstate, query), fields(UserId=%access_token.user_id,request="/generated/content[delete]",id=%query.id))]
 pub async fn delete_request(Extension(access_token): Extension<AccessTokenDescription>, State(appstate): State<AppState>, Query(query): Query<GenerationQuery>) -> Result<&'static str, ServerResponse> {
@@ -385,10 +599,11 @@ pub async fn delete_request(Extension(access_token): Extension<AccessTokenDescri
                                 return Ok(Err(status_response(StatusCode::LOCKED, "Cannot cancel a generation")));
                             }
                             // If generation status is Waiting then we'll flag this to be deleted
-                            // later (by the generator function)
+                            // later (the generator drops its message and aws-lambda-generate-reaper
+                            // removes the row)
                             if let GenerationStatus::Waiting = generation_details.status {
                                 let set_records = diesel::update(generation::table.filter(generation::userid.eq(access_token.user_id).and(generation::jobid.eq(uuid_job_id))))
-                                                            .set(generation::status.eq(GenerationStatus::Deleting))
+                                                            .set((generation::status.eq(GenerationStatus::Deleting), generation::lastchanged.eq(chrono::Utc::now().naive_utc())))
                                                             .execute(conn)
                                                             .await?;
                                 if set_records == 0 {
@@ -400,16 +615,11 @@ pub async fn delete_request(Extension(access_token): Extension<AccessTokenDescri
                             }
                             // If generation status is Success we will delete object from S3
                             if let GenerationStatus::Success = generation_details.status {
-                                let _ = appstate.s3_client.delete_object()
-                                                            .bucket(&*Constants::GENERATED_BUCKET_NAME)
-                                                            .key(format!("{uuid_job_id}.rapidl.gz"))
-                                                            .send()
-                                                            .await.map_err(|err| {
-                                                                match err.as_service_error() {
-                                                                    err @_ => tracing::error!("Unhandelled S3 DeleteObjectError: {:?}", err),
-                                                                }
-                                                                diesel::result::Error::RollbackTransaction
-                                                            })?;
+                                appstate.paper_store.delete(uuid_job_id)
+                                                    .await.map_err(|err| {
+                                                        tracing::error!("Failed to delete paper {uuid_job_id}, {err}");
+                                                        diesel::result::Error::RollbackTransaction
+                                                    })?;
                             }
                             // Delete record
                             let deleted_records = diesel::delete(generation::table.filter(generation::userid.eq(access_token.user_id).and(generation::jobid.eq(uuid_job_id)))).execute(conn).await?;
@@ -426,13 +636,17 @@ pub async fn delete_request(Extension(access_token): Extension<AccessTokenDescri
                         })?;
     }
 
-    if let Ok(_) = ret {
+    if let Ok(outcome) = ret {
         // Delete from cache if possible, ignore any error, the keys have a short TTL anyway
         if let Ok(mut redis_conn) = appstate.redis.get().await {
             let _ = cmd("DEL")
                     .arg(&[&format!("gen:job:{uuid_job_id}")])
                     .query_async::<_, ()>(&mut redis_conn)
                     .await;
+            // Answers any status long polls waiting on the job
+            if outcome == "Deleting" {
+                let _ = Progress::publish(&mut redis_conn, uuid_job_id, &ProgressEvent::new(GenerationStatus::Deleting, 0)).await;
+            }
         }
     }
 
@@ -1,5 +1,6 @@
-use diesel::{Selectable, Queryable};
-use db_schema::{generation, hooked_sql_types::GenerationStatus};
+use diesel::{Selectable, Queryable, Insertable};
+use db_schema::{generation, markedanswers, hooked_sql_types::GenerationStatus};
+use common_types::Generate::{SQSBody, Locale, PaperComposition, str_to_generation_options, str_to_generation_id};
 use serde::{Deserialize, Deserializer};
 use chrono::NaiveDateTime;
 use garde::Validate;
@@ -10,6 +11,29 @@ pub struct GenerationQuery {
     pub id: String,
 }
 
+#[derive(Deserialize, Validate)]
+pub struct GenerationContentQuery {
+    #[garde(ascii)]
+    pub id: String,
+    // Clients that understand the RPDL header ask for the blob exactly as stored
+    #[serde(default)]
+    #[garde(skip)]
+    pub framed: bool,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct GenerationEventsQuery {
+    #[garde(ascii)]
+    pub id: String,
+    // The last event the client was sent, left out on the first request
+    #[serde(default)]
+    #[garde(skip)]
+    pub status: Option<GenerationStatus>,
+    #[serde(default)]
+    #[garde(skip)]
+    pub progress: Option<u8>,
+}
+
 #[derive(Deserialize, Validate)]
 pub struct GenerationNameChangeQuery {
     #[garde(ascii)]
@@ -43,6 +67,35 @@ pub struct GenerationSelectable {
     pub options: String,
     pub category: String,
     pub creditsused: i16,
+    pub composition: Option<String>,
+    pub locale: String,
+    pub refundedat: Option<NaiveDateTime>,
+    pub personalised: bool,
+}
+
+impl GenerationSelectable {
+    // Rebuilds the queue record the generation was created from, None if any of the stored data is bad
+    pub fn to_sqs_body(&self, user_id: i64, job_id: uuid::Uuid) -> Option<SQSBody> {
+        let composition = match self.composition {
+            Some(ref composition) => Some(serde_json::from_str::<PaperComposition>(composition).ok()?),
+            None => None,
+        };
+        // Personalised rows have no GenerateId to parse, their category is only for display
+        let gen_id = match self.personalised {
+            true => None,
+            false => Some(str_to_generation_id(&self.category).ok()?),
+        };
+        Some(SQSBody {
+            gen_id,
+            user_id,
+            created_at: self.createdat,
+            job_id: job_id.to_string(),
+            opts: str_to_generation_options(&self.options).ok()?,
+            composition,
+            locale: self.locale.parse::<Locale>().ok()?,
+            personalised: self.personalised,
+        })
+    }
 }
 
 #[derive(Queryable, Selectable, PartialEq, Debug)]
@@ -57,3 +110,34 @@ pub struct GenerationSelectableWithJobId {
     pub category: String,
     pub creditsused: i16,
 }
+
+#[derive(Deserialize, Validate)]
+pub struct MarkedAnswersPayload {
+    #[garde(ascii)]
+    pub id: String,
+    #[garde(length(min=1, max=60), dive)]
+    pub answers: Vec<MarkedAnswer>,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct MarkedAnswer {
+    // As stored against the generation, "{template id}:{hash}"
+    #[garde(ascii, length(min=1, max=96))]
+    pub fingerprint: String,
+    #[garde(range(min=0, max=50))]
+    pub awarded: i16,
+    #[garde(range(min=1, max=50))]
+    pub available: i16,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = markedanswers)]
+pub struct InsertableMarkedAnswer {
+    pub userid: i64,
+    pub jobid: uuid::Uuid,
+    pub templateid: String,
+    pub topic: String,
+    pub marksawarded: i16,
+    pub marksavailable: i16,
+    pub answeredat: NaiveDateTime,
+}
@@ -1,17 +1,21 @@
 #[allow(non_snake_case)]
 pub mod Generate {
+    use ::std::collections::HashMap;
     use ::std::fmt;
     use ::std::str;
     use chrono::NaiveDateTime;
     use serde::{Deserialize, Serialize};
 
-    #[derive(Deserialize, Serialize, Debug)]
+    #[derive(Deserialize, Serialize, Debug, Clone)]
     pub enum GenerateId {
         MathsMechanics,
         MathsStatistics,
         MathsCore,
     }
 
+    // Shown where a category would be, personalised papers are picked across every category
+    pub const PERSONALISED_CATEGORY: &str = "Personalised";
+
     impl fmt::Display for GenerateId {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             write!(f, "{:?}", self)
@@ -35,9 +39,87 @@ pub mod Generate {
     pub struct SQSBody {
         pub user_id: i64,
         pub job_id: String,
-        pub gen_id: GenerateId,
+        // None for personalised papers
+        pub gen_id: Option<GenerateId>,
         pub opts: Vec<GenerateOption>,
         pub created_at: NaiveDateTime,
+        // Missing on messages queued before papers could be composed
+        #[serde(default)]
+        pub composition: Option<PaperComposition>,
+        #[serde(default)]
+        pub locale: Locale,
+        // Topics are picked by the generator from the user's marked answers rather than by the user
+        #[serde(default)]
+        pub personalised: bool,
+    }
+
+    // Language the questions are written in, the message catalogs themselves live with the generator
+    #[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq, Hash)]
+    pub enum Locale {
+        #[default]
+        #[serde(rename = "en-GB")]
+        EnGb,
+        #[serde(rename = "cy")]
+        Cy,
+    }
+
+    impl fmt::Display for Locale {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Locale::EnGb => write!(f, "en-GB"),
+                Locale::Cy => write!(f, "cy"),
+            }
+        }
+    }
+
+    impl str::FromStr for Locale {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "en-GB" => Ok(Locale::EnGb),
+                "cy" => Ok(Locale::Cy),
+                _ => Err(format!("'{}' is not a valid Locale", s)),
+            }
+        }
+    }
+
+    // Papers made without a composition get this many questions per topic, and
+    // one credit buys this many questions
+    pub const QUESTIONS_PER_TOPIC: usize = 3;
+    pub const QUESTIONS_PER_CREDIT: usize = 3;
+
+    #[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+    pub enum PaperOrdering {
+        #[default]
+        Grouped,
+        Interleaved,
+    }
+
+    #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+    pub struct PaperComposition {
+        pub length: usize,
+        // Relative share of the paper for each topic, topics left out have a weight of 1
+        #[serde(default)]
+        pub weights: HashMap<GenerateOption, u32>,
+        #[serde(default)]
+        pub ordering: PaperOrdering,
+    }
+
+    impl PaperComposition {
+        pub fn per_topic(topics: usize) -> Self {
+            Self {
+                length: topics * QUESTIONS_PER_TOPIC,
+                weights: HashMap::new(),
+                ordering: PaperOrdering::Grouped,
+            }
+        }
+        pub fn weight_of(&self, option: &GenerateOption) -> u32 {
+            self.weights.get(option).copied().unwrap_or(1)
+        }
+        pub fn required_credits(&self) -> i32 {
+            self.length.div_ceil(QUESTIONS_PER_CREDIT) as i32
+        }
     }
 
     #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
@@ -103,6 +185,10 @@ pub mod Generate {
         id.as_ref().parse()
     }
     pub fn str_to_generation_options<T: AsRef<str>>(options: T) -> Result<Vec<GenerateOption>, String> {
+        // Personalised generations have no options until the generator picks them
+        if options.as_ref().trim().is_empty() {
+            return Ok(Vec::new());
+        }
         options.as_ref()
             .split(',')
             .map(|x| x.trim().parse::<GenerateOption>())
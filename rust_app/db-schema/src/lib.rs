@@ -15,7 +15,7 @@ pub mod sql_types {
 pub mod hooked_sql_types {
     use serde::Deserialize;
 
-    #[derive(Debug, PartialEq, Clone, diesel_derive_enum::DbEnum, serde::Serialize)]
+    #[derive(Deserialize, Debug, PartialEq, Clone, diesel_derive_enum::DbEnum, serde::Serialize)]
     #[ExistingTypePath = "crate::sql_types::GenerationStatusMapping"]
     pub enum GenerationStatus {
         Working,
@@ -69,6 +69,58 @@ diesel::table! {
         options -> Text,
         category -> Varchar,
         creditsused -> SmallInt,
+        composition -> Nullable<Text>,
+        fingerprints -> Nullable<Text>,
+        #[max_length = 8]
+        locale -> Varchar,
+        attempts -> Int4,
+        failurereason -> Nullable<Text>,
+        refundedat -> Nullable<Timestamp>,
+        lastchanged -> Timestamp,
+        notifybyemail -> Bool,
+        // Unique together with userid, cleared once IDEMPOTENCY_WINDOW_SECS has passed so the key can be used again
+        idempotencykey -> Nullable<Text>,
+        idempotencyhash -> Nullable<Text>,
+        personalised -> Bool,
+    }
+}
+
+diesel::table! {
+    generationdeadletters (id) {
+        id -> Int8,
+        jobid -> Uuid,
+        userid -> Int8,
+        body -> Text,
+        reason -> Text,
+        attempts -> Int4,
+        createdat -> Timestamp,
+        requeuedat -> Nullable<Timestamp>,
+        requeuedby -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    generationqueue (id) {
+        id -> Int8,
+        body -> Text,
+        receivecount -> Int4,
+        visibleat -> Timestamp,
+        createdat -> Timestamp,
+    }
+}
+
+diesel::table! {
+    markedanswers (id) {
+        id -> Int8,
+        userid -> Int8,
+        jobid -> Uuid,
+        #[max_length = 64]
+        templateid -> Varchar,
+        #[max_length = 32]
+        topic -> Varchar,
+        marksawarded -> SmallInt,
+        marksavailable -> SmallInt,
+        answeredat -> Timestamp,
     }
 }
 
@@ -130,12 +182,17 @@ diesel::table! {
 
 diesel::joinable!(allocatedcredits -> users (userid));
 diesel::joinable!(generation -> users (userid));
+diesel::joinable!(generationdeadletters -> users (userid));
+diesel::joinable!(markedanswers -> users (userid));
 diesel::joinable!(supportticketmessages -> supporttickets (ticketid));
 diesel::joinable!(supporttickets -> users (claimedby));
 
 diesel::allow_tables_to_appear_in_same_query!(
     allocatedcredits,
     generation,
+    generationdeadletters,
+    generationqueue,
+    markedanswers,
     supportticketmessages,
     supporttickets,
     users,
@@ -0,0 +1,114 @@
+// Runs the question engine locally without any of the AWS plumbing, for template authors
+// and for poking at papers that have already been generated
+
+use ::std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use common_types::Generate::{GenerateId, GenerateOption, Locale, PaperComposition, PaperOrdering};
+use aws_lambda_generate::generate::{
+    engine,
+    export::{html, latex, markdown},
+    paper::{blob, Paper},
+};
+
+#[derive(Parser)]
+#[command(name = "rapidl-gen", about = "Generate and inspect rapidl papers offline")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a new paper
+    Generate {
+        #[arg(long)]
+        category: GenerateId,
+        /// Comma separated, e.g. SUVAT,Vectors
+        #[arg(long, value_delimiter = ',', required = true)]
+        options: Vec<GenerateOption>,
+        /// Same seed and options always give the same questions
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Number of root questions, defaults to three per option
+        #[arg(long)]
+        length: Option<usize>,
+        #[arg(long)]
+        interleaved: bool,
+        /// en-GB or cy
+        #[arg(long, default_value = "en-GB")]
+        locale: Locale,
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        /// Written to stdout when not given
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Decode an existing .rapidl.gz
+    Decode {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    /// Bare msgpack Paper, without the blob header or gzip
+    Msgpack,
+    /// Exactly what the generate lambda puts in S3
+    Rapidl,
+    Latex,
+    LatexMarkScheme,
+    Html,
+    Markdown,
+}
+
+fn render(paper: &Paper, format: Format) -> Result<Vec<u8>, String> {
+    match format {
+        Format::Json => serde_json::to_vec_pretty(paper).map_err(|err| format!("Failed to serialise paper as json, {err}")),
+        Format::Msgpack => rmp_serde::to_vec(paper).map_err(|err| format!("Failed to serialise paper as msgpack, {err}")),
+        Format::Rapidl => blob::encode(paper).map_err(|err| format!("Failed to encode paper, {err:?}")),
+        Format::Latex => Ok(latex::render_question_paper(paper).into_bytes()),
+        Format::LatexMarkScheme => Ok(latex::render_mark_scheme(paper).into_bytes()),
+        Format::Html => Ok(html::render(paper).into_bytes()),
+        Format::Markdown => Ok(markdown::render(paper).into_bytes()),
+    }
+}
+
+fn write_output(bytes: &[u8], output: Option<PathBuf>) -> Result<(), String> {
+    match output {
+        Some(path) => fs::write(&path, bytes).map_err(|err| format!("Failed to write {}, {err}", path.display())),
+        None => io::stdout().write_all(bytes).map_err(|err| format!("Failed to write to stdout, {err}")),
+    }
+}
+
+fn main() -> Result<(), String> {
+    match Cli::parse().command {
+        Command::Generate { category, options, seed, length, interleaved, locale, format, output } => {
+            if let Some(seed) = seed {
+                engine::seed(seed);
+            }
+            let composition = PaperComposition {
+                length: length.unwrap_or(options.len() * common_types::Generate::QUESTIONS_PER_TOPIC),
+                weights: Default::default(),
+                ordering: if interleaved { PaperOrdering::Interleaved } else { PaperOrdering::Grouped },
+            };
+            let mut paper = Paper::new(0, Some(category), options, Some(composition)).with_locale(locale);
+            paper.populate().map_err(|err| format!("Failed to generate paper, {err:?}"))?;
+            write_output(&render(&paper, format)?, output)
+        },
+        Command::Decode { file, format, output } => {
+            let bytes = fs::read(&file).map_err(|err| format!("Failed to read {}, {err}", file.display()))?;
+            let (header, paper) = blob::decode(&bytes).map_err(|err| format!("Failed to decode {}, {err:?}", file.display()))?;
+            eprintln!("schema version {}, generator version {}", header.schema_version, header.generator_version.as_deref().unwrap_or("unknown"));
+            write_output(&render(&paper, format)?, output)
+        },
+    }
+}